@@ -0,0 +1,75 @@
+/// What has to happen for an achievement to unlock
+#[derive(Debug, Copy, Clone)]
+pub enum Goal {
+    /// Climb this high in a single run
+    Height(f32),
+    /// Attach this many times, counted across every run
+    TotalAttaches(u32),
+    /// Stay alive this many seconds in a single run
+    Survive(f32),
+}
+
+#[derive(Debug)]
+pub struct Achievement {
+    /// Stored in the save data, so never change one once released
+    pub id: &'static str,
+    pub name: &'static str,
+    pub description: &'static str,
+    pub goal: Goal,
+}
+
+/// Add new achievements here; nothing else needs to know about them
+pub const ACHIEVEMENTS: [Achievement; 4] = [
+    Achievement {
+        id: "height-50",
+        name: "Getting Somewhere",
+        description: "Reach level 50",
+        goal: Goal::Height(50.0),
+    },
+    Achievement {
+        id: "height-100",
+        name: "High Flyer",
+        description: "Reach level 100",
+        goal: Goal::Height(100.0),
+    },
+    Achievement {
+        id: "attaches-100",
+        name: "Hooked",
+        description: "Attach to 100 nodes",
+        goal: Goal::TotalAttaches(100),
+    },
+    Achievement {
+        id: "survive-60",
+        name: "Survivor",
+        description: "Stay alive for 60 seconds",
+        goal: Goal::Survive(60.0),
+    },
+];
+
+/// Everything goals are checked against
+#[derive(Debug)]
+pub struct Progress {
+    pub height: f32,
+    pub total_attaches: u32,
+    pub run_time: f32,
+}
+
+impl Goal {
+    pub fn is_met(&self, progress: &Progress) -> bool {
+        match *self {
+            Goal::Height(height) => progress.height >= height,
+            Goal::TotalAttaches(count) => progress.total_attaches >= count,
+            Goal::Survive(time) => progress.run_time >= time,
+        }
+    }
+}
+
+/// Achievements `progress` has earned that aren't in `unlocked` yet
+pub fn newly_unlocked<'a>(
+    progress: &'a Progress,
+    unlocked: &'a [String],
+) -> impl Iterator<Item = &'static Achievement> + 'a {
+    ACHIEVEMENTS
+        .iter()
+        .filter(move |a| a.goal.is_met(progress) && !unlocked.iter().any(|id| id == a.id))
+}
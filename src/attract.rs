@@ -0,0 +1,62 @@
+use crate::{direction, Attach, CollisionCause, Sim};
+
+/// Least upward the launch direction can be for the autopilot to let go
+const RELEASE_MIN_UP: f32 = 0.7;
+/// Seconds to wait for a targeted node to line up before giving up on it
+const TARGET_PATIENCE: f32 = 1.0;
+/// Seconds of free flight after letting go before pressing again, so it
+/// doesn't grab the node it's only just left
+const PRESS_DELAY: f32 = 0.15;
+
+/// An autopilot flying a course behind the title menu, arcade attract style.
+/// It presses and lets go the way a player would, through the same
+/// selection and attach calls, and starts over whenever it crashes.
+#[derive(Debug)]
+pub struct Attract {
+    pub sim: Sim,
+    /// Counts down to the next press while flying free, or to giving up
+    /// on a targeted node
+    wait: f32,
+}
+
+impl Attract {
+    pub fn new(sim: Sim) -> Attract {
+        Attract {
+            sim,
+            wait: PRESS_DELAY,
+        }
+    }
+
+    /// Plays `dt` more of the run
+    pub fn update(&mut self, dt: f32) {
+        self.wait -= dt;
+        match self.sim.attached_node {
+            Attach::None if self.wait <= 0.0 => {
+                self.sim.handle_button_press(0.0, 0);
+                self.wait = TARGET_PATIENCE;
+            }
+            Attach::TARGET(..) if self.wait <= 0.0 => {
+                self.sim.detach();
+                self.wait = 0.0;
+            }
+            Attach::SUCCESS(..) if self.should_release() => {
+                self.sim.detach();
+                self.wait = PRESS_DELAY;
+            }
+            _ => (),
+        }
+        self.sim.step(dt);
+        let crashed = self.sim.handle_collision() != CollisionCause::None;
+        if crashed || self.sim.has_finished() || self.sim.detect_non_finite() {
+            self.sim.reset();
+            self.wait = PRESS_DELAY;
+        }
+    }
+
+    /// Whether letting go now would launch up the course and away from
+    /// the nearer wall
+    fn should_release(&self) -> bool {
+        let heading = direction(self.sim.player.facing);
+        heading.y > RELEASE_MIN_UP && heading.x * self.sim.player.pos.x <= 0.0
+    }
+}
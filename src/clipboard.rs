@@ -0,0 +1,42 @@
+use crate::error::{Error, Result};
+use std::io::Write;
+use std::process::{Command, Stdio};
+
+/// Clipboard tools to try in order, with the arguments that make them read
+/// the text to copy from stdin
+#[cfg(target_os = "macos")]
+const TOOLS: &[(&str, &[&str])] = &[("pbcopy", &[])];
+#[cfg(target_os = "windows")]
+const TOOLS: &[(&str, &[&str])] = &[("clip", &[])];
+#[cfg(not(any(target_os = "macos", target_os = "windows")))]
+const TOOLS: &[(&str, &[&str])] = &[
+    ("wl-copy", &[]),
+    ("xclip", &["-selection", "clipboard"]),
+    ("xsel", &["--clipboard", "--input"]),
+];
+
+/// Puts `text` on the system clipboard by handing it to the platform's
+/// clipboard tool, the first of `TOOLS` that's installed and works
+pub fn copy(text: &str) -> Result<()> {
+    for (program, args) in TOOLS {
+        let Ok(mut child) = Command::new(program)
+            .args(*args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .spawn()
+        else {
+            continue;
+        };
+        // Dropping stdin closes it, which tells the tool the text is complete
+        let written = child
+            .stdin
+            .take()
+            .is_some_and(|mut stdin| stdin.write_all(text.as_bytes()).is_ok());
+        let succeeded = child.wait().is_ok_and(|status| status.success());
+        if written && succeeded {
+            return Ok(());
+        }
+    }
+    Err(Error::ClipboardUnavailable)
+}
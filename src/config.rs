@@ -0,0 +1,152 @@
+use crate::error::Result;
+use crate::menu::cycle;
+use crate::save::read_json;
+use crate::{AREA_WIDTH, ATTACH_REACH, PLAYER_BBOX, PLAYER_SPEED};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Gameplay tuning. Normally comes from the chosen `Difficulty`, but a JSON
+/// file in the user config dir replaces the preset outright. The game never
+/// writes that file; it's there for experimenting with course generation
+/// and difficulty by hand.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct GameConfig {
+    pub min_node_radius: f32,
+    pub max_node_radius: f32,
+    /// Size of the player's hitbox
+    pub player_bbox: f32,
+    /// Free-flight speed at the start of a run
+    pub player_speed: f32,
+    /// Average vertical gap between generated nodes
+    pub node_spacing: f32,
+    pub corridor_width: f32,
+    /// Seconds before the panic detach can be used again
+    pub panic_cooldown: f32,
+    /// Height above which wind blows, when it's switched on
+    pub wind_height: f32,
+    /// Furthest a node can be from the player and still be attached to
+    pub attach_reach: f32,
+    /// Largest orbit an attach may start, so far off nodes that happen to
+    /// line up can't be swung round slowly. No limit when left out.
+    pub max_orbit_radius: Option<f32>,
+}
+
+impl Default for GameConfig {
+    fn default() -> GameConfig {
+        Difficulty::Normal.config()
+    }
+}
+
+impl GameConfig {
+    /// The config file if there is one, otherwise `None` to use a preset
+    pub fn load(path: &Path) -> Result<Option<GameConfig>> {
+        read_json(path)
+    }
+
+    /// The node radius bounds, in order and kept to a sane size
+    pub fn node_radius_range(&self) -> (f32, f32) {
+        let min = self.min_node_radius.clamp(0.01, 1.0);
+        let max = self.max_node_radius.clamp(0.01, 1.0);
+        (min.min(max), min.max(max))
+    }
+}
+
+/// Named bundles of `GameConfig` values, picked on the title menu
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum Difficulty {
+    Easy,
+    #[default]
+    Normal,
+    Hard,
+}
+
+pub const DIFFICULTIES: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Normal, Difficulty::Hard];
+
+impl Difficulty {
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "Easy",
+            Difficulty::Normal => "Normal",
+            Difficulty::Hard => "Hard",
+        }
+    }
+
+    pub fn config(self) -> GameConfig {
+        match self {
+            Difficulty::Easy => GameConfig {
+                min_node_radius: 0.08,
+                max_node_radius: 0.3,
+                player_bbox: 0.04,
+                player_speed: 3.5,
+                node_spacing: 1.3,
+                corridor_width: AREA_WIDTH * 1.15,
+                panic_cooldown: 1.5,
+                wind_height: 50.0,
+                attach_reach: ATTACH_REACH,
+                max_orbit_radius: None,
+            },
+            Difficulty::Normal => GameConfig {
+                min_node_radius: 0.05,
+                max_node_radius: 0.25,
+                player_bbox: PLAYER_BBOX,
+                player_speed: PLAYER_SPEED,
+                node_spacing: 1.5,
+                corridor_width: AREA_WIDTH,
+                panic_cooldown: 2.0,
+                wind_height: 30.0,
+                attach_reach: ATTACH_REACH,
+                max_orbit_radius: None,
+            },
+            Difficulty::Hard => GameConfig {
+                min_node_radius: 0.04,
+                max_node_radius: 0.18,
+                player_bbox: 0.06,
+                player_speed: 4.5,
+                node_spacing: 1.7,
+                corridor_width: AREA_WIDTH * 0.9,
+                panic_cooldown: 3.0,
+                wind_height: 15.0,
+                attach_reach: ATTACH_REACH,
+                max_orbit_radius: None,
+            },
+        }
+    }
+
+    /// The next preset along, wrapping round, `step` being -1 or 1
+    pub fn cycle(self, step: i32) -> Difficulty {
+        cycle(&DIFFICULTIES, self, step > 0)
+    }
+}
+
+/// How generated nodes pick their colour from the palette. Under colour
+/// rules the colour decides the node's kind too.
+#[derive(Debug, Copy, Clone, PartialEq, Default, Serialize, Deserialize)]
+pub enum ColorStrategy {
+    /// Straight through the palette in order, as in classic play
+    #[default]
+    Sequential,
+    Random,
+    /// Smallest nodes get the first colour, largest the last
+    BySize,
+}
+
+const COLOR_STRATEGIES: [ColorStrategy; 3] = [
+    ColorStrategy::Sequential,
+    ColorStrategy::Random,
+    ColorStrategy::BySize,
+];
+
+impl ColorStrategy {
+    pub fn name(self) -> &'static str {
+        match self {
+            ColorStrategy::Sequential => "In order",
+            ColorStrategy::Random => "Random",
+            ColorStrategy::BySize => "By size",
+        }
+    }
+
+    pub fn cycle(self, step: i32) -> ColorStrategy {
+        cycle(&COLOR_STRATEGIES, self, step > 0)
+    }
+}
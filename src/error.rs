@@ -0,0 +1,39 @@
+use ggez::GameError;
+use std::io;
+use std::path::PathBuf;
+
+/// Errors from loading and saving the game's own files, with enough
+/// context to tell which file was the problem
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("Couldn't load asset {path}: {source}")]
+    AssetMissing { path: String, source: GameError },
+    #[error("Couldn't access {}: {source}", path.display())]
+    SaveLoad { path: PathBuf, source: io::Error },
+    #[error("Malformed {}: {source}", path.display())]
+    ConfigParse {
+        path: PathBuf,
+        source: serde_json::Error,
+    },
+    #[error("Palette {} has no colours", path.display())]
+    EmptyPalette { path: PathBuf },
+    #[error("Snapshot {} is from a different version of the game", path.display())]
+    SnapshotVersion { path: PathBuf, found: Option<u64> },
+    #[error("Couldn't reach the clipboard")]
+    ClipboardUnavailable,
+}
+
+pub type Result<T> = std::result::Result<T, Error>;
+
+impl From<Error> for GameError {
+    fn from(e: Error) -> GameError {
+        match e {
+            Error::AssetMissing { .. } => GameError::ResourceLoadError(e.to_string()),
+            Error::SaveLoad { .. } => GameError::FilesystemError(e.to_string()),
+            Error::ConfigParse { .. }
+            | Error::EmptyPalette { .. }
+            | Error::SnapshotVersion { .. } => GameError::ConfigError(e.to_string()),
+            Error::ClipboardUnavailable => GameError::CustomError(e.to_string()),
+        }
+    }
+}
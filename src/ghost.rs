@@ -0,0 +1,73 @@
+use crate::error::Result;
+use crate::save::{read_json, write_json};
+use ggez::glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Runs are sampled at a fixed rate rather than once per frame, so a
+/// recording plays back in step with a live run at any frame rate
+const SAMPLE_TIME: f32 = 1.0 / 30.0;
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
+pub struct Sample {
+    pub pos: Vec2,
+    pub facing: f32,
+}
+
+/// The recorded path of a single run
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+pub struct Run {
+    pub height: f32,
+    pub samples: Vec<Sample>,
+}
+
+impl Run {
+    /// Where the run was `time` seconds in, or `None` once it has ended
+    pub fn sample_at(&self, time: f32) -> Option<Sample> {
+        let t = time / SAMPLE_TIME;
+        let i = t.floor() as usize;
+        let a = self.samples.get(i)?;
+        let b = self.samples.get(i + 1).unwrap_or(a);
+        Some(Sample {
+            pos: a.pos.lerp(b.pos, t.fract()),
+            facing: a.facing,
+        })
+    }
+
+    /// Loads a saved run, or `None` if this course hasn't been played yet
+    pub fn load(path: &Path) -> Result<Option<Run>> {
+        read_json(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_json(path, self)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Recorder {
+    run: Run,
+    elapsed: f32,
+    next_sample: f32,
+}
+
+impl Recorder {
+    pub fn record(&mut self, dt: f32, pos: Vec2, facing: f32) {
+        while self.elapsed >= self.next_sample {
+            self.run.samples.push(Sample { pos, facing });
+            self.next_sample += SAMPLE_TIME;
+        }
+        self.elapsed += dt;
+        self.run.height = self.run.height.max(pos.y);
+    }
+
+    /// Seconds since the run started
+    pub fn elapsed(&self) -> f32 {
+        self.elapsed
+    }
+
+    /// Ends the current run, returning its recording and starting afresh
+    pub fn finish(&mut self) -> Run {
+        std::mem::take(self).run
+    }
+}
@@ -0,0 +1,48 @@
+use crate::error::Result;
+use crate::save::{read_json, write_json};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// How many scores the board keeps
+pub const LEADERBOARD_SIZE: usize = 10;
+/// Characters in a name on the board, arcade style
+pub const INITIALS_LEN: usize = 3;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Entry {
+    pub initials: String,
+    pub score: u32,
+}
+
+/// The best classic scores, highest first, stored in the user data dir
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Leaderboard {
+    pub entries: Vec<Entry>,
+}
+
+impl Leaderboard {
+    /// Loads the board, or an empty one if nothing's been saved yet
+    pub fn load(path: &Path) -> Result<Leaderboard> {
+        read_json(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_json(path, self)
+    }
+
+    /// Whether `score` would earn a place on the board
+    pub fn qualifies(&self, score: u32) -> bool {
+        score > 0
+            && (self.entries.len() < LEADERBOARD_SIZE
+                || self.entries.last().is_some_and(|e| score > e.score))
+    }
+
+    /// Adds a score in its place, below any equal ones already there,
+    /// dropping whatever falls off the bottom
+    pub fn insert(&mut self, initials: String, score: u32) {
+        let index = self.entries.partition_point(|e| e.score >= score);
+        self.entries.insert(index, Entry { initials, score });
+        self.entries.truncate(LEADERBOARD_SIZE);
+    }
+}
@@ -0,0 +1,36 @@
+use crate::error::{Error, Result};
+use crate::save::write_json;
+use crate::Node;
+use glam::Vec2;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// A course layout as stored on disk. Kept as a struct rather than a
+/// bare list so more per-level data can be added without breaking old files.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Level {
+    pub nodes: Vec<Node>,
+    /// Height of the finish line. Defaults to the top node.
+    #[serde(default)]
+    pub finish_y: Option<f32>,
+    /// Where the player begins. Defaults to the bottom of the corridor.
+    #[serde(default)]
+    pub start: Option<Vec2>,
+}
+
+pub fn save_level(path: &Path, level: &Level) -> Result<()> {
+    write_json(path, level)
+}
+
+pub fn load_level(path: &Path) -> Result<Level> {
+    // Unlike settings, a level that's been asked for has to exist
+    let json = fs::read_to_string(path).map_err(|source| Error::SaveLoad {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    serde_json::from_str(&json).map_err(|source| Error::ConfigParse {
+        path: path.to_path_buf(),
+        source,
+    })
+}
@@ -0,0 +1,14 @@
+use env_logger::Env;
+
+/// Environment variable setting how much gets logged, in `env_logger`'s
+/// filter syntax: `off`, `error`, `warn`, `info`, `debug` or `trace`, or
+/// per crate like `first_game=debug`
+pub const LOG_ENV: &str = "ONE_MORE_LINE_LOG";
+/// Used when `LOG_ENV` is unset. Enough to see what went wrong without
+/// chatter on every run.
+const DEFAULT_FILTER: &str = "warn";
+
+/// Installs the logger, writing to stderr at the level `LOG_ENV` asks for
+pub fn init() {
+    env_logger::Builder::from_env(Env::new().filter_or(LOG_ENV, DEFAULT_FILTER)).init();
+}
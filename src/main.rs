@@ -1,19 +1,190 @@
+use achievements::{Progress, ACHIEVEMENTS};
+use attract::Attract;
+use config::{ColorStrategy, Difficulty, GameConfig};
+use ggez::audio::SoundSource;
 use ggez::glam::Vec2;
-use ggez::input::keyboard::{KeyCode, KeyInput};
+use ggez::input::keyboard::{KeyCode, KeyInput, KeyMods};
 use ggez::*;
+use ghost::{Recorder, Run};
+use leaderboard::{Leaderboard, INITIALS_LEN};
+use log::{debug, error, info, warn};
+use menu::Menu;
 use oorandom::Rand32;
+use save::SaveData;
+use serde::{Deserialize, Serialize};
+use settings::{SettingItem, Settings, MAX_CAMERA_LOOKAHEAD, MIN_CAMERA_LOOKAHEAD, SETTING_ITEMS};
+use snapshot::{Snapshot, SNAPSHOT_VERSION};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::VecDeque;
 use std::env;
 use std::f32::consts::PI;
+use std::hash::{Hash, Hasher};
 use std::path;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+mod achievements;
+mod attract;
+mod clipboard;
+mod config;
+mod error;
+mod ghost;
+mod leaderboard;
+mod level;
+mod logger;
+mod menu;
+mod palette;
+mod save;
+mod settings;
+mod snapshot;
+mod telemetry;
 
 const SCREEN_HEIGHT: f32 = 848.0;
 const SCREEN_WIDTH: f32 = 480.0;
 const AREA_HEIGHT: f32 = 5.0;
 const AREA_WIDTH: f32 = (SCREEN_WIDTH / SCREEN_HEIGHT) * AREA_HEIGHT;
 const MAX_TIME_OUTSIDE: f32 = 0.5;
+/// Screen height at which menus start listing items, below the title
+const MENU_TOP: f32 = 50.0;
+/// Screen height given to each menu item
+const MENU_ROW_HEIGHT: f32 = 20.0;
 const RATIO: f32 = SCREEN_HEIGHT / AREA_HEIGHT;
+/// Longest step the sim takes in one frame. A hitch longer than this slows
+/// the game down instead of letting the player jump through a node or wall.
+const MAX_FRAME_TIME: f32 = 0.05;
+/// How many sim steps each frame takes while fast-forwarding in debug mode
+const FAST_FORWARD_STEPS: u32 = 4;
+/// How long a press that found nothing to attach to keeps being retried
+const INPUT_BUFFER_TIME: f32 = 0.15;
+const PLAYER_BBOX: f32 = 0.05;
+const PLAYER_SPEED: f32 = 4.0;
+/// Attaches land once the cosine of the angle between the player's heading
+/// and the node falls below this, i.e. within about 6 degrees of side on
+const ATTACH_ALIGNMENT: f32 = 0.1;
+/// Default `GameConfig::attach_reach`, in world units
+const ATTACH_REACH: f32 = 2.0;
+/// A node within this sine of the angle of dead ahead (or dead behind)
+/// counts as in line with the player, and is orbited clockwise
+const COLLINEAR_TOLERANCE: f32 = 1e-4;
+/// With thrust control, free flight speed stays within this range
+const MIN_THRUST_SPEED: f32 = 2.0;
+const MAX_THRUST_SPEED: f32 = 6.0;
+/// How quickly holding up or down changes speed, per second
+const THRUST_ACCELERATION: f32 = 4.0;
+/// Adaptive difficulty aims for runs of about this many seconds
+const TARGET_RUN_TIME: f32 = 20.0;
+const RECENT_RUNS: usize = 5;
+/// Passing a node within this multiple of the collision distance counts as a near miss
+const NEAR_MISS_RATIO: f32 = 1.5;
+const NEAR_MISS_COOLDOWN: f32 = 0.5;
+const NEAR_MISS_FLASH_TIME: f32 = 0.25;
+/// Length of the combo timer bar when full, in pixels
+const COMBO_BAR_WIDTH: f32 = 100.0;
+/// Length of the alignment meter, in pixels before line scaling
+const ALIGNMENT_METER_WIDTH: f32 = 40.0;
+/// How visible the attract mode's run is behind the title menu
+const ATTRACT_ALPHA: f32 = 0.35;
+/// Only the most recent attach points are marked, like the trail only keeps its end
+const MAX_BREADCRUMBS: usize = 100;
+/// Decorative nodes scroll at this fraction of the camera's speed
+const BACKGROUND_PARALLAX: f32 = 0.5;
+/// World units between the faint horizontal lines behind everything
+const SCANLINE_SPACING: f32 = 0.5;
+/// How far the drawn tether bows per unit of orbit speed, in world units
+const TETHER_BOW: f32 = 0.02;
+/// With constant angular speed on, every orbit turns as fast as the
+/// default physics would turn one of this radius
+const ORBIT_REFERENCE_RADIUS: f32 = 0.5;
+/// Roughly how long smooth orbit entry takes to settle on the orbit's radius
+const ORBIT_SMOOTH_TIME: f32 = 0.08;
+/// Attach assist only kicks in once a targeted node is within this (as the
+/// cosine of the angle) of being side on, which is about 30 degrees
+const ATTACH_ASSIST_WINDOW: f32 = 0.5;
+/// Radians per second attach assist turns the player at full strength
+const ATTACH_ASSIST_TURN: f32 = 4.0;
+/// The orbit arc shows where the player will be this many seconds from now
+const ORBIT_PREVIEW_TIME: f32 = 0.25;
+/// The launch preview traces this many seconds of flight as this many dots
+const LAUNCH_PREVIEW_TIME: f32 = 0.6;
+const LAUNCH_PREVIEW_DOTS: usize = 12;
+/// In versus, falling this far behind the other player knocks you out,
+/// since the camera can't keep you both in view any further apart
+const VERSUS_MAX_GAP: f32 = 3.5;
+/// Trail and sprite tint for the second player in versus
+const RIVAL_COLOR: graphics::Color = graphics::Color::new(1.0, 0.5, 0.2, 1.0);
+/// How long everything freezes on a crash before the run resets
+const HITSTOP_TIME: f32 = 0.08;
+/// Seconds on the time attack clock at the start of a run
+const TIME_ATTACK_START: f32 = 15.0;
+/// Seconds each attach adds to the time attack clock
+const TIME_ATTACK_BONUS: f32 = 1.5;
+/// Height between checkpoints in checkpoints mode
+const CHECKPOINT_SPACING: f32 = 25.0;
+/// How far clear of every node a checkpoint respawn has to be, as a multiple
+/// of the distance that counts as touching it
+const SPAWN_CLEARANCE: f32 = 2.0;
+/// Minimum gap between attach sounds, so quick re-attaches don't pile up
+const ATTACH_SOUND_COOLDOWN: f32 = 0.08;
+/// Seconds the nodes take to grow in at the start of a run
+const INTRO_TIME: f32 = 0.5;
+/// How long an achievement notification stays up
+const TOAST_TIME: f32 = 3.0;
+/// Walls start glowing once the player is this close, with the wall warning on
+const WALL_WARNING_DISTANCE: f32 = 0.5;
+/// Width of the red band inside each wall while the player is far from it.
+/// It doubles on reaching the wall.
+const DANGER_ZONE_WIDTH: f32 = 0.15;
+/// Bands of fading red the danger zone is built from
+const DANGER_ZONE_STRIPS: usize = 6;
+/// Strongest sideways push of a gust, in units per second
+const WIND_STRENGTH: f32 = 1.2;
+/// Seconds for the wind to blow one way, then the other
+const WIND_PERIOD: f32 = 6.0;
+const WIND_PARTICLES: usize = 40;
+/// Points of trail still drawn in performance mode
+const PERFORMANCE_TRAIL_POINTS: usize = 30;
+/// Furthest the glitchy trail shifts a point, in pixels
+const GLITCH_OFFSET: f32 = 3.0;
+/// How many times a second the glitchy trail changes shape
+const GLITCH_RATE: f32 = 12.0;
+/// Trail segments per palette colour in the rainbow trail
+const RAINBOW_SEGMENTS: f32 = 8.0;
+/// Palette colours a second the rainbow trail's colours drift by
+const RAINBOW_SPEED: f32 = 2.0;
+/// Performance mode tessellates circles this many times more coarsely
+const PERFORMANCE_TOLERANCE: f32 = 4.0;
+/// Climbing this far blends the background fully into the next zone's colour
+const ZONE_HEIGHT: f32 = 50.0;
+/// Background colours for each zone, in climbing order, looping once exhausted.
+/// Kept close to black so nodes and the trail still stand out.
+const ZONE_COLORS: [(f32, f32, f32); 4] = [
+    (0.0, 0.02, 0.1),
+    (0.07, 0.0, 0.1),
+    (0.1, 0.0, 0.04),
+    (0.0, 0.07, 0.07),
+];
+/// Downward acceleration in gravity mode, in world units per second squared
+const GRAVITY: f32 = 1.5;
+const BONUS_MULTIPLIER: u32 = 3;
+const STEER_WEIGHT: f32 = 1.0;
+const FRAME_GRAPH_LEN: usize = 120;
+/// Debug velocity arrows show how far the player would go in this many seconds
+const VELOCITY_ARROW_TIME: f32 = 0.25;
+/// World units the debug mouse wheel moves the player per notch
+const SCRUB_STEP: f32 = 1.5;
+/// Seconds of immunity after a debug scrub, to get clear of whatever it landed in
+const SCRUB_GRACE_TIME: f32 = 0.5;
+const EXPORT_PATH: &str = "level.json";
+/// Environment variable that can point the game at its assets, like `--resources`
+const RESOURCES_ENV: &str = "ONE_MORE_LINE_RESOURCES";
+const SETTINGS_FILE: &str = "settings.json";
+const SAVE_FILE: &str = "save.json";
+const PALETTE_FILE: &str = "palette.json";
+const CONFIG_FILE: &str = "config.json";
+const TELEMETRY_FILE: &str = "telemetry.log";
+const LEADERBOARD_FILE: &str = "leaderboard.json";
+const SNAPSHOT_FILE: &str = "snapshot.json";
 
+/// The built-in node palette, used unless the player supplies their own
 const COLORS: [graphics::Color; 6] = [
     graphics::Color::WHITE,
     graphics::Color::MAGENTA,
@@ -23,13 +194,125 @@ const COLORS: [graphics::Color; 6] = [
     graphics::Color::YELLOW,
 ];
 
-#[derive(Debug)]
+/// `COLORS` as seen in colourblind mode, using the Okabe-Ito palette
+const COLORBLIND_COLORS: [graphics::Color; 6] = [
+    graphics::Color::WHITE,
+    graphics::Color::new(0.8, 0.47, 0.65, 1.0),
+    graphics::Color::new(0.34, 0.71, 0.91, 1.0),
+    graphics::Color::new(0.0, 0.62, 0.45, 1.0),
+    graphics::Color::new(0.84, 0.37, 0.0, 1.0),
+    graphics::Color::new(0.94, 0.89, 0.26, 1.0),
+];
+
+/// Maps a node colour to the one to draw, keeping its alpha
+fn display_color(color: graphics::Color, colorblind: bool) -> graphics::Color {
+    if !colorblind {
+        return color;
+    }
+    match COLORS
+        .iter()
+        .position(|c| (c.r, c.g, c.b) == (color.r, color.g, color.b))
+    {
+        Some(i) => graphics::Color {
+            a: color.a,
+            ..COLORBLIND_COLORS[i]
+        },
+        None => color,
+    }
+}
+
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 enum Attach {
     SUCCESS(Node, bool),
     TARGET(Node, bool),
     None,
 }
 
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+enum GameMode {
+    Classic,
+    /// No deaths, walls just stop the player
+    Zen,
+    /// Free flight falls, so you lose height if you don't keep attaching
+    Gravity,
+    /// Two players on one keyboard racing up the same course
+    Versus,
+    /// A clock counts down and every attach buys a little more time
+    TimeAttack,
+    /// Dying sends the player back to the last checkpoint passed, not the start
+    Checkpoints,
+}
+
+impl GameMode {
+    /// Whether runs count towards lifetime stats and achievements. Zen
+    /// can't die, checkpoints take the sting out of dying and versus is
+    /// shared, so none of them would be fair.
+    fn tracks_progress(self) -> bool {
+        matches!(
+            self,
+            GameMode::Classic | GameMode::Gravity | GameMode::TimeAttack
+        )
+    }
+}
+
+const GAME_MODES: [(GameMode, &str); 6] = [
+    (GameMode::Classic, "Classic"),
+    (GameMode::Zen, "Zen"),
+    (GameMode::Gravity, "Gravity"),
+    (GameMode::TimeAttack, "Time attack"),
+    (GameMode::Checkpoints, "Checkpoints"),
+    (GameMode::Versus, "Two players"),
+];
+
+/// What, if anything, ended the run
+#[derive(Debug, Copy, Clone, PartialEq, Serialize)]
+enum CollisionCause {
+    None,
+    /// Touched a wall while flying free
+    Side,
+    Node,
+    /// Stayed beyond the walls for longer than `MAX_TIME_OUTSIDE`
+    OutsideTooLong,
+    /// The time attack clock ran out
+    TimeUp,
+}
+
+impl CollisionCause {
+    /// What to tell the player about a run that ended this way
+    fn message(self) -> Option<&'static str> {
+        match self {
+            CollisionCause::None => None,
+            CollisionCause::Side => Some("Hit the wall"),
+            CollisionCause::Node => Some("Hit a node"),
+            CollisionCause::OutsideTooLong => Some("Strayed too far"),
+            CollisionCause::TimeUp => Some("Ran out of time"),
+        }
+    }
+}
+
+/// Rows of the title menu
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum MenuItem {
+    Play(GameMode),
+    Difficulty,
+    Settings,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+enum Screen {
+    Menu,
+    Settings,
+    Playing,
+    /// Typing initials for a score that made the leaderboard
+    NameEntry,
+}
+
+/// Unit vector a player with the given `facing` moves along. Facing is the
+/// sprite's clockwise rotation from straight up, so 0.0 is +Y and PI / 2.0 is +X.
+fn direction(facing: f32) -> Vec2 {
+    Vec2::from_angle(PI / 2.0 - facing)
+}
+
 /// Translates the world coordinate system, which
 /// has Y pointing up and the origin at the bottom middle,
 /// to the screen coordinate system, which has Y
@@ -52,36 +335,76 @@ fn world_to_screen_coords(
 struct Assets {
     player_image: graphics::Image,
     hit_sound: audio::Source,
+    /// Played on a successful attach. Optional, so a missing file just means silence.
+    attach_sound: Option<audio::Source>,
+    /// Drawn tinted in place of each node's circle when the setting's on.
+    /// Optional too, falling back to circles without it.
+    node_image: Option<graphics::Image>,
 }
 
 impl Assets {
-    fn new(ctx: &mut Context) -> GameResult<Assets> {
-        let player_image =
-            graphics::Image::from_path(ctx, "/player.png").expect("Can't load player image");
-        let hit_sound = audio::Source::new(ctx, "/boom.ogg").expect("Can't load hit sound");
+    fn new(ctx: &mut Context) -> error::Result<Assets> {
+        let player_image = graphics::Image::from_path(ctx, "/player.png").map_err(|source| {
+            error::Error::AssetMissing {
+                path: "/player.png".to_string(),
+                source,
+            }
+        })?;
+        let hit_sound =
+            audio::Source::new(ctx, "/boom.ogg").map_err(|source| error::Error::AssetMissing {
+                path: "/boom.ogg".to_string(),
+                source,
+            })?;
+        let attach_sound = match audio::Source::new(ctx, "/latch.wav") {
+            Ok(sound) => Some(sound),
+            Err(e) => {
+                warn!("No attach sound: {}", e);
+                None
+            }
+        };
+        let node_image = match graphics::Image::from_path(ctx, "/node.png") {
+            Ok(image) => Some(image),
+            Err(e) => {
+                warn!("No node sprite: {}", e);
+                None
+            }
+        };
+        debug!(
+            "Assets loaded, with{} attach sound and with{} node sprite",
+            if attach_sound.is_some() { "" } else { "out" },
+            if node_image.is_some() { "" } else { "out" }
+        );
         Ok(Assets {
             player_image,
             hit_sound,
+            attach_sound,
+            node_image,
         })
     }
 }
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 struct Player {
     pos: Vec2,
     speed: f32,
     facing: f32,
     bbox: f32,
     time_disconnected: f32,
+    /// Only differs from `speed` along `facing` while falling in gravity mode
+    velocity: Vec2,
+    /// The radius an orbit is still easing towards, with smooth orbit entry on
+    target_radius: Option<f32>,
 }
 
 impl Player {
-    fn new() -> GameResult<Player> {
+    fn new(start: Vec2) -> GameResult<Player> {
         Ok(Player {
-            pos: Vec2::ZERO,
-            speed: 4.0,
+            pos: start,
+            speed: PLAYER_SPEED,
             facing: 0.0,
-            bbox: 0.05,
+            bbox: PLAYER_BBOX,
             time_disconnected: 0.0,
+            velocity: Vec2::new(0.0, 4.0),
+            target_radius: None,
         })
     }
 
@@ -92,6 +415,7 @@ impl Player {
         origin: Vec2,
         screen_w: f32,
         screen_h: f32,
+        color: graphics::Color,
     ) {
         let image = &assets.player_image;
 
@@ -99,76 +423,536 @@ impl Player {
         let drawparams = graphics::DrawParam::new()
             .dest(pos)
             .rotation(self.facing)
-            .offset(Vec2::new(0.5, 0.5));
+            .offset(Vec2::new(0.5, 0.5))
+            .color(color);
         canvas.draw(image, drawparams);
     }
 
-    fn orbit(self: &mut Player, node: &Node, dt: f32, is_clockwise: bool) {
+    /// Radians per second around a node `radius` away. Normally the player
+    /// keeps their linear speed, so wide orbits turn slower than tight ones.
+    fn angular_speed(&self, radius: f32, constant_angular_speed: bool) -> f32 {
+        if constant_angular_speed {
+            self.speed / ORBIT_REFERENCE_RADIUS
+        } else {
+            self.speed / radius
+        }
+    }
+
+    /// Swings the player `dt` further round `node`, tightening the orbit
+    /// by `decay` world units a second. A decaying orbit ends up touching the
+    /// node, which is as fatal as flying into it.
+    fn orbit(
+        self: &mut Player,
+        node: &Node,
+        dt: f32,
+        is_clockwise: bool,
+        constant_angular_speed: bool,
+        decay: f32,
+    ) {
         let mult = if is_clockwise { -1.0 } else { 1.0 };
         let fac = if is_clockwise { Vec2::NEG_X } else { Vec2::X };
         let radius = node.pos.distance(self.pos);
-        let circ = 2.0 * PI * radius;
-        let period = circ / self.speed;
+        let angular_speed = self.angular_speed(radius, constant_angular_speed);
         let delta = self.pos - node.pos;
-        self.pos = Vec2::from_angle(mult * 2.0 * PI * dt / period).rotate(delta) + node.pos;
+        let mut rotated = Vec2::from_angle(mult * angular_speed * dt).rotate(delta);
+        if decay > 0.0 {
+            // Stops at the node's edge, where zen mode has to leave the player
+            let tightened = (radius - decay * dt).max(node.radius);
+            rotated = rotated.normalize_or_zero() * tightened;
+            if let Some(target) = &mut self.target_radius {
+                *target = (*target - decay * dt).max(node.radius);
+            }
+        }
+        if let Some(target) = self.target_radius {
+            let eased = radius + (target - radius) * (dt / ORBIT_SMOOTH_TIME).min(1.0);
+            rotated = rotated.normalize_or_zero() * eased;
+            if (target - eased).abs() < 1e-3 {
+                self.target_radius = None;
+            }
+        }
+        self.pos = rotated + node.pos;
         self.facing = delta.angle_between(fac);
     }
+
+    /// Free flight. Without gravity the player just keeps going the way
+    /// they're facing; with it they accelerate downward and turn to face
+    /// the way they're actually moving.
+    fn fly(self: &mut Player, dt: f32, gravity: f32) {
+        if gravity == 0.0 {
+            self.velocity = self.speed * direction(self.facing);
+        } else {
+            self.velocity.y -= gravity * dt;
+            self.facing = PI / 2.0 - self.velocity.y.atan2(self.velocity.x);
+        }
+        self.pos += self.velocity * dt;
+    }
+}
+
+/// Distance from `point` to the closest point on the segment from `a` to `b`
+fn segment_distance(a: Vec2, b: Vec2, point: Vec2) -> f32 {
+    let ab = b - a;
+    let length_squared = ab.length_squared();
+    if length_squared == 0.0 {
+        return point.distance(a);
+    }
+    let t = ((point - a).dot(ab) / length_squared).clamp(0.0, 1.0);
+    point.distance(a + ab * t)
+}
+
+/// A small shift for drawing the trail point at `point` with, seeded from the
+/// point itself so each one holds steady between the glitch's jumps
+fn glitch_offset(point: Vec2, time: f32) -> Vec2 {
+    let tick = (time * GLITCH_RATE) as u64;
+    let seed = ((point.x.to_bits() as u64) << 32 | point.y.to_bits() as u64) ^ tick;
+    let mut rng = Rand32::new(seed);
+    Vec2::new(rng.rand_float() - 0.5, rng.rand_float() - 0.5) * 2.0 * GLITCH_OFFSET
+}
+
+/// Borderless fullscreen at the desktop's resolution, or a normal window
+fn fullscreen_type(fullscreen: bool) -> conf::FullscreenType {
+    if fullscreen {
+        conf::FullscreenType::Desktop
+    } else {
+        conf::FullscreenType::Windowed
+    }
+}
+
+/// The window's drawable size, or the windowed size while it has none.
+/// Some platforms report zero for a moment at startup or when minimised,
+/// which would scale everything down to nothing.
+fn usable_size((width, height): (f32, f32)) -> (f32, f32) {
+    if width >= 1.0 && height >= 1.0 {
+        (width, height)
+    } else {
+        (SCREEN_WIDTH, SCREEN_HEIGHT)
+    }
+}
+
+/// The sim time to step for a frame that took `delta` seconds
+fn frame_time(delta: f32) -> f32 {
+    delta.min(MAX_FRAME_TIME)
+}
+
+/// Draws a trail through `points`, skipping repeats. The tessellator fails
+/// on zero-length segments, which a player who hasn't moved would produce.
+fn add_trail(mb: &mut graphics::MeshBuilder, points: &[Vec2], width: f32, color: graphics::Color) {
+    let distinct = distinct_points(points);
+    if distinct.len() < 2 {
+        return;
+    }
+    if let Err(e) = mb.line(&distinct, width, color) {
+        error!("Couldn't draw trail: {}", e);
+    }
+}
+
+/// `add_trail` with each segment its own colour, from `rainbow_color`.
+/// The colours drift along the trail as `time` goes on.
+fn add_rainbow_trail(
+    mb: &mut graphics::MeshBuilder,
+    points: &[Vec2],
+    width: f32,
+    time: f32,
+    colorblind: bool,
+) {
+    let distinct = distinct_points(points);
+    for (i, segment) in distinct.windows(2).enumerate() {
+        let phase = i as f32 / RAINBOW_SEGMENTS - time * RAINBOW_SPEED;
+        let color = rainbow_color(phase, colorblind);
+        if let Err(e) = mb.line(segment, width, color) {
+            error!("Couldn't draw trail: {}", e);
+            return;
+        }
+    }
+}
+
+/// `points` without repeats, which the tessellator can't draw a line through
+fn distinct_points(points: &[Vec2]) -> Vec<Vec2> {
+    let mut distinct: Vec<Vec2> = points.to_vec();
+    distinct.dedup_by(|a, b| a.distance_squared(*b) < 1e-6);
+    distinct
+}
+
+/// The node palette as a loop, `phase` 0.0 being its first colour, 1.0 the
+/// next and so on, blending in between
+fn rainbow_color(phase: f32, colorblind: bool) -> graphics::Color {
+    let phase = phase.rem_euclid(COLORS.len() as f32);
+    let i = phase.floor() as usize % COLORS.len();
+    let t = phase.fract();
+    let from = display_color(COLORS[i], colorblind);
+    let to = display_color(COLORS[(i + 1) % COLORS.len()], colorblind);
+    graphics::Color::new(
+        from.r + (to.r - from.r) * t,
+        from.g + (to.g - from.g) * t,
+        from.b + (to.b - from.b) * t,
+        1.0,
+    )
+}
+
+/// Picks a tessellation tolerance for a circle of the given on-screen radius.
+/// Big circles get a finer tolerance so they don't look faceted, while small
+/// ones stay at the old 1.0 since extra segments there are invisible anyway.
+fn circle_tolerance(pixel_radius: f32) -> f32 {
+    (8.0 / pixel_radius).clamp(0.1, 1.0)
+}
+
+/// Background colour at the given height, eased between neighbouring zones
+fn background_color(height: f32) -> graphics::Color {
+    let zone = height.max(0.0) / ZONE_HEIGHT;
+    let (from, to) = (
+        ZONE_COLORS[zone as usize % ZONE_COLORS.len()],
+        ZONE_COLORS[(zone as usize + 1) % ZONE_COLORS.len()],
+    );
+    let t = zone.fract();
+    let t = t * t * (3.0 - 2.0 * t);
+    graphics::Color::new(
+        from.0 + (to.0 - from.0) * t,
+        from.1 + (to.1 - from.1) * t,
+        from.2 + (to.2 - from.2) * t,
+        1.0,
+    )
+}
+
+/// What a node does beyond being orbited, decided by its colour.
+/// Only honoured when colour rules are switched on.
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
+enum NodeKind {
+    #[default]
+    Normal,
+    /// Worth extra points
+    Bonus,
+    /// Can't be attached to
+    Hazard,
+}
+
+impl NodeKind {
+    fn from_color_index(i: usize) -> NodeKind {
+        match i {
+            4 => NodeKind::Hazard,
+            5 => NodeKind::Bonus,
+            _ => NodeKind::Normal,
+        }
+    }
 }
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Serialize, Deserialize)]
 struct Node {
     pos: Vec2,
     radius: f32,
     color: graphics::Color,
+    #[serde(default)]
+    kind: NodeKind,
+    /// Set once released from in one-time mode, after which it can't be grabbed
+    /// again. Only meaningful during a run, so never saved with a level.
+    #[serde(skip)]
+    used: bool,
 }
 
 impl Node {
+    /// Smaller nodes are harder to grab, so they're worth more
+    fn points(self: &Node) -> u32 {
+        (0.5 / self.radius).round() as u32
+    }
+
+    /// Adds the node's circle to `mb`, tessellated more finely the higher `detail` is
+    #[allow(clippy::too_many_arguments)]
     fn add_mesh(
         self: &Node,
         mb: &mut graphics::MeshBuilder,
         origin: Vec2,
         screen_w: f32,
         screen_h: f32,
+        detail: f32,
+        colorblind: bool,
+        reveal: f32,
     ) {
+        if reveal <= 0.0 {
+            return;
+        }
         let pos = world_to_screen_coords(screen_w, screen_h, self.pos, origin);
-        mb.circle(
+        let pixel_radius = self.radius * RATIO * reveal;
+        let color = self.draw_color(colorblind, reveal);
+        if let Err(e) = mb.circle(
             graphics::DrawMode::fill(),
             pos,
-            self.radius * RATIO,
-            1.0,
-            self.color,
-        )
-        .expect("Something went wrong rendering a node");
+            pixel_radius,
+            circle_tolerance(pixel_radius) / detail,
+            color,
+        ) {
+            error!("Couldn't draw a node: {}", e);
+        }
+    }
+
+    /// The sprite alternative to `add_mesh`, stretching `image` over the node
+    #[allow(clippy::too_many_arguments)]
+    fn draw_sprite(
+        self: &Node,
+        canvas: &mut graphics::Canvas,
+        image: &graphics::Image,
+        origin: Vec2,
+        screen_w: f32,
+        screen_h: f32,
+        colorblind: bool,
+        reveal: f32,
+    ) {
+        if reveal <= 0.0 {
+            return;
+        }
+        let pos = world_to_screen_coords(screen_w, screen_h, self.pos, origin);
+        let pixel_radius = self.radius * RATIO * reveal;
+        let scale = Vec2::new(
+            2.0 * pixel_radius / image.width() as f32,
+            2.0 * pixel_radius / image.height() as f32,
+        );
+        let drawparams = graphics::DrawParam::new()
+            .dest(pos)
+            .offset(Vec2::new(0.5, 0.5))
+            .scale(scale)
+            .color(self.draw_color(colorblind, reveal));
+        canvas.draw(image, drawparams);
+    }
+
+    /// Faded while used up or still appearing
+    fn draw_color(&self, colorblind: bool, reveal: f32) -> graphics::Color {
+        let mut color = display_color(self.color, colorblind);
+        if self.used {
+            color.a *= 0.3;
+        }
+        color.a *= reveal;
+        color
+    }
+}
+
+/// A mesh kept between frames and only rebuilt when its key changes
+#[derive(Debug, Default)]
+struct MeshCache {
+    key: u64,
+    mesh: Option<graphics::Mesh>,
+}
+
+impl MeshCache {
+    /// The cached mesh, rebuilt with `build` first if `key` has changed.
+    /// `None` if `build` adds nothing, since an empty mesh can't be drawn.
+    fn get(
+        &mut self,
+        ctx: &Context,
+        key: u64,
+        build: impl FnOnce(&mut graphics::MeshBuilder),
+    ) -> Option<&graphics::Mesh> {
+        if key != self.key || self.mesh.is_none() {
+            let mb = &mut graphics::MeshBuilder::new();
+            build(mb);
+            let data = mb.build();
+            self.key = key;
+            self.mesh = if data.vertices.is_empty() {
+                None
+            } else {
+                Some(graphics::Mesh::from_data(ctx, data))
+            };
+        }
+        self.mesh.as_ref()
+    }
+}
+
+/// Identifies how a set of nodes would be drawn, so a cached mesh of them
+/// can tell when it's stale. `extra` covers the display settings involved.
+fn node_mesh_key(nodes: &[Node], extra: &[f32]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    for n in nodes {
+        [n.pos.x, n.pos.y, n.radius, n.color.r, n.color.g, n.color.b]
+            .map(f32::to_bits)
+            .hash(&mut hasher);
+        n.used.hash(&mut hasher);
+    }
+    for value in extra {
+        value.to_bits().hash(&mut hasher);
     }
+    hasher.finish()
 }
 
 #[derive(Debug)]
 struct State {
     assets: Assets,
-    player: Player,
-    nodes: Vec<Node>,
-    attached_node: Attach,
+    sim: Sim,
+    /// The autopilot playing behind the title menu
+    attract: Attract,
+    /// Purely decorative, so kept out of the sim entirely
+    background_nodes: Vec<Node>,
     screen_width: f32,
     screen_height: f32,
-    prev_points: Vec<Vec2>,
+    /// Multiplier for line widths and circle smoothness so high-DPI displays
+    /// don't get thin, jagged lines
+    line_scale: f32,
+    screen: Screen,
+    main_menu: Menu<MenuItem>,
+    settings_menu: Menu<SettingItem>,
+    buffered_press_timer: f32,
+    /// Which way the arrow keys are biasing attach selection, -1.0 to 1.0
+    steer: f32,
+    /// How many of the best attach candidates `Tab` has passed over
+    target_skip: usize,
+    settings: Settings,
+    config_dir: path::PathBuf,
+    data_dir: path::PathBuf,
+    /// Identifies the course being played, so ghosts are only raced on the same layout
+    course_id: String,
+    /// Seed the current course was generated from, if it was generated at all
+    seed: Option<u64>,
+    /// Shared randomness for anything generated after the course itself
+    rng: Rand32,
+    recorder: Recorder,
+    best_run: Option<Run>,
+    score: u32,
+    /// Highest point reached this run, so falling back down doesn't lose progress
+    max_height: f32,
+    /// Where checkpoints mode respawns, the first clear spot past the last
+    /// checkpoint line
+    checkpoint: Option<Vec2>,
+    save_data: SaveData,
+    leaderboard: Leaderboard,
+    /// The score waiting for initials on the name entry screen, and what's been typed so far
+    pending_score: u32,
+    initials: String,
+    recent_runs: Vec<f32>,
+    ease: f32,
+    near_miss_cooldown: f32,
+    near_miss_flash: f32,
+    /// Attaches chained so far without dying or, with the combo timer on,
+    /// letting `combo_timer` run out
+    combo: u32,
+    /// Seconds left to attach again before the combo breaks
+    combo_timer: f32,
+    debug: bool,
+    frame_times: VecDeque<f32>,
+    /// When the current frame began, for the frame rate cap
+    frame_start: Instant,
+    /// Paused with `P`, as opposed to automatically by losing focus
+    paused: bool,
+    focus_lost: bool,
+    /// Notifications on screen, with how many seconds each has left
+    toasts: Vec<(String, f32)>,
+    /// Colours generated nodes are drawn from
+    palette: Vec<graphics::Color>,
+    /// The second player in versus, flying the same course
+    rival: Option<Sim>,
+    /// Rounds won by each player since versus was picked from the menu
+    versus_wins: [u32; 2],
+    /// Counts down the freeze after a crash, which ends in a reset
+    hitstop_timer: f32,
+    /// Time until another attach sound is allowed
+    attach_sound_cooldown: f32,
+    /// Counts down the immunity after scrubbing with the mouse wheel
+    scrub_timer: f32,
+    /// Set once the debug scrub is used, so the run can't set records
+    scrubbed: bool,
+    /// Time attack clock, only counting down in that mode
+    time_left: f32,
+    /// Counts down while the nodes grow in at the start of a run. Nothing
+    /// can be hit until it runs out.
+    intro_timer: f32,
+    /// Counts down while the screen fades in from black after a change of
+    /// screen or a new run. Keys are ignored until it runs out.
+    fade_timer: f32,
+    /// Why the current run ended, kept until it's logged
+    death_cause: CollisionCause,
+    /// How the previous run ended, shown while the next one starts
+    last_death: CollisionCause,
+    /// The end of the previous run's trail, drawn faintly to show where it went wrong
+    last_trail: Vec<Vec2>,
+    /// Where the player was at each attach this run, marked along the path
+    breadcrumbs: Vec<Vec2>,
+    config: GameConfig,
+    /// Seconds into the wind's cycle
+    wind_time: f32,
+    /// Screen positions of the specks that show which way the wind is blowing
+    wind_particles: Vec<Vec2>,
+    /// Nodes are built relative to the world origin and shifted into place
+    /// when drawn, so these only need rebuilding when the nodes change
+    background_mesh: MeshCache,
+    node_mesh: MeshCache,
 }
 
-fn make_nodes(begin: u32, end: u32, mut rng: Rand32) -> Vec<Node> {
+fn make_nodes(
+    begin: u32,
+    end: u32,
+    rng: &mut Rand32,
+    palette: &[graphics::Color],
+    config: &GameConfig,
+    strategy: ColorStrategy,
+    lanes: u32,
+) -> Vec<Node> {
+    let (min_radius, max_radius) = config.node_radius_range();
+    // Colours get their own generator, drawn from whatever the strategy,
+    // so switching strategy never moves the nodes themselves
+    let mut color_rng = Rand32::new(rng.rand_u32() as u64);
     (begin..=end)
         .map(|i| {
-            let y = rng.rand_float() - 0.5 + 1.5 * (i as f32);
-            let x = AREA_WIDTH * (rng.rand_float() - 0.5);
+            let y = rng.rand_float() - 0.5 + config.node_spacing * (i as f32);
+            let mut x = config.corridor_width * (rng.rand_float() - 0.5);
+            if lanes > 0 {
+                // Snap to the middle of whichever lane x fell in
+                let lane_width = config.corridor_width / lanes as f32;
+                let lane = ((x / lane_width + lanes as f32 / 2.0).floor() as i32)
+                    .clamp(0, lanes as i32 - 1);
+                x = (lane as f32 + 0.5) * lane_width - config.corridor_width / 2.0;
+            }
+            let radius = (rng.rand_float() * (max_radius - min_radius)) + min_radius;
+            let color_index = match strategy {
+                ColorStrategy::Sequential => i as usize % palette.len(),
+                ColorStrategy::Random => color_rng.rand_range(0..palette.len() as u32) as usize,
+                ColorStrategy::BySize => {
+                    let size = if max_radius > min_radius {
+                        (radius - min_radius) / (max_radius - min_radius)
+                    } else {
+                        0.0
+                    };
+                    ((size * palette.len() as f32) as usize).min(palette.len() - 1)
+                }
+            };
             Node {
                 pos: Vec2 { x, y },
-                radius: (rng.rand_float() * (0.25 - 0.05)) + 0.05,
-                color: COLORS[(i % 6) as usize],
+                radius,
+                color: palette[color_index],
+                kind: NodeKind::from_color_index(color_index),
+                used: false,
+            }
+        })
+        .collect()
+}
+
+/// Height of the highest node, where a course ends unless told otherwise
+fn course_top(nodes: &[Node]) -> f32 {
+    nodes.iter().map(|n| n.pos.y).fold(0.0, f32::max)
+}
+
+/// Non-interactive nodes scattered behind the playfield for a sense of depth.
+/// They're spread over the parallax-scaled height of the course so they
+/// last all the way to the top.
+fn make_background_nodes(
+    count: usize,
+    course_top: f32,
+    rng: &mut Rand32,
+    palette: &[graphics::Color],
+) -> Vec<Node> {
+    let height = course_top * BACKGROUND_PARALLAX + AREA_HEIGHT;
+    (0..count)
+        .map(|i| {
+            let color = palette[i % palette.len()];
+            Node {
+                pos: Vec2::new(
+                    AREA_WIDTH * (rng.rand_float() - 0.5),
+                    height * rng.rand_float(),
+                ),
+                radius: (rng.rand_float() * (0.1 - 0.02)) + 0.02,
+                color: graphics::Color::new(color.r, color.g, color.b, 0.25),
+                kind: NodeKind::Normal,
+                used: false,
             }
         })
         .collect()
 }
 
 fn get_cross_point(player: &Player, node: &Node) -> Vec2 {
-    let vel = Vec2::from_angle(PI / 2.0 - player.facing).normalize();
+    let vel = direction(player.facing);
     let player_to_node = node.pos - player.pos;
     let angle = vel.angle_between(player_to_node);
     let dist = angle.cos() * player_to_node.length() * vel;
@@ -176,165 +960,498 @@ fn get_cross_point(player: &Player, node: &Node) -> Vec2 {
     cross_point
 }
 fn get_is_behind(player: &Player, node: &Node) -> bool {
-    let vel = Vec2::from_angle(PI / 2.0 - player.facing).normalize();
+    let vel = direction(player.facing);
     let player_to_node = node.pos - player.pos;
     let angle = vel.angle_between(player_to_node);
     return angle.cos() < 0.0;
 }
 
-fn filter_deadly_nodes(player: &Player, node: &Node) -> bool {
+fn filter_deadly_nodes(player: &Player, node: &Node, half_width: f32, reach: f32) -> bool {
     let cross_point = get_cross_point(player, node);
     let is_behind = get_is_behind(player, node);
-    let is_outside = cross_point.x.abs() > AREA_WIDTH / 2.0;
+    let is_outside = cross_point.x.abs() > half_width;
     let is_hitting = node.pos.distance(cross_point) < player.bbox + node.radius;
-    let is_far_away = player.pos.distance(node.pos) > 2.0;
-    !(is_outside || is_hitting || is_far_away || is_behind)
+    let is_far_away = player.pos.distance(node.pos) > reach;
+    !(is_outside || is_hitting || is_far_away || is_behind || node.used)
+}
+
+/// How much to favour `node` when the player is steering towards a side
+/// (-1.0 left, 1.0 right), in the same squared-distance units the attach
+/// selection compares. Zero when not steering.
+fn steer_bias(player: &Player, node: &Node, steer: f32) -> f32 {
+    let heading = direction(player.facing);
+    let right = Vec2::new(heading.y, -heading.x);
+    STEER_WEIGHT * steer * (node.pos - player.pos).dot(right)
 }
 
 fn filter_hitting_nodes(player: &Player, node: &Node) -> bool {
     let cross_point = get_cross_point(player, node);
     let is_hitting = node.pos.distance(cross_point) < player.bbox + node.radius;
-    !is_hitting
+    !(is_hitting || node.used)
 }
 
 fn get_is_clockwise(player: &Player, node: &Node) -> bool {
     let delta = player.pos - node.pos;
-    let angle = delta.angle_between(Vec2::from_angle(PI / 2.0 - player.facing));
-    let is_clockwise = angle < 0.0;
+    let angle = delta.angle_between(direction(player.facing));
+    let is_collinear = angle.sin().abs() < COLLINEAR_TOLERANCE;
+    let is_clockwise = angle < 0.0 || is_collinear;
     is_clockwise
 }
 
-impl State {
-    fn new(ctx: &mut Context) -> GameResult<State> {
-        let start = SystemTime::now();
-        let since_the_epoch = start
-            .duration_since(UNIX_EPOCH)
-            .expect("Time went backwards");
-        let (width, height) = ctx.gfx.drawable_size();
-        let rng = Rand32::new(since_the_epoch.as_secs());
-        let player = Player::new()?;
-        let nodes = make_nodes(0, 100, rng);
-        let assets = Assets::new(ctx)?;
+/// How much to ease off (positive) or tighten up (negative), in -1..=1,
+/// based on how long recent runs lasted. A run that's still going counts
+/// once it outlasts the target, so long survival tightens things mid-run.
+fn difficulty_ease(recent_runs: &[f32], current_run: f32) -> f32 {
+    let mut total: f32 = recent_runs.iter().sum();
+    let mut count = recent_runs.len();
+    if current_run > TARGET_RUN_TIME {
+        total += current_run;
+        count += 1;
+    }
+    if count == 0 {
+        return 0.0;
+    }
+    let average = total / count as f32;
+    ((TARGET_RUN_TIME - average) / TARGET_RUN_TIME).clamp(-1.0, 1.0)
+}
+
+/// The game world and its rules, kept apart from rendering, assets and
+/// persistence so it can be stepped without a `Context`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Sim {
+    player: Player,
+    /// Where the player begins each run
+    start: Vec2,
+    nodes: Vec<Node>,
+    attached_node: Attach,
+    prev_points: Vec<Vec2>,
+    /// Player position before the last step, so collision can check the whole move
+    prev_pos: Vec2,
+    /// Reaching this height completes the course
+    finish_y: f32,
+    mode: GameMode,
+    /// Half the corridor width, which adaptive difficulty can nudge
+    half_width: f32,
+    /// Furthest a node can be and still be attached to, from `GameConfig`
+    attach_reach: f32,
+    /// Largest orbit an attach may start, from `GameConfig`
+    max_orbit_radius: Option<f32>,
+    /// Whether node colours carry gameplay meaning, see `NodeKind`
+    color_rules: bool,
+    /// Whether nodes can only be attached to once per run
+    one_time_nodes: bool,
+    /// Whether every orbit turns at the same rate, see `Player::angular_speed`
+    constant_angular_speed: bool,
+    /// Whether attaching eases onto the orbit, see `Player::target_radius`
+    smooth_orbit_entry: bool,
+    /// How fast attach assist turns the player, in radians per second. 0.0 is off.
+    attach_assist: f32,
+    /// Seconds before an orbit lets go by itself, 0.0 for no limit
+    max_orbit_time: f32,
+    /// World units per second orbits tighten by, see `Player::orbit`
+    orbit_decay: f32,
+    /// Seconds spent on the current orbit
+    orbit_time: f32,
+    /// Forces every orbit clockwise (`true`) or anticlockwise (`false`)
+    /// instead of picking the way the player is already heading
+    orbit_lock: Option<bool>,
+    /// Free-flight speed each run starts at
+    cruise_speed: f32,
+    /// Time until the panic detach can be used again
+    panic_timer: f32,
+    /// Sideways drift applied in free flight, in units per second
+    wind: f32,
+    /// Speed up (positive) or slow down (negative) input, -1.0 to 1.0.
+    /// Stays at zero unless thrust control is on.
+    thrust: f32,
+    /// Successful attaches so far this run
+    attaches: u32,
+    /// Seconds survived so far this run
+    run_time: f32,
+    /// Path length flown so far this run, orbits included
+    distance: f32,
+}
 
-        Ok(State {
-            player,
+impl Sim {
+    fn new(nodes: Vec<Node>) -> GameResult<Sim> {
+        Ok(Sim {
+            finish_y: course_top(&nodes),
+            player: Player::new(Vec2::ZERO)?,
+            start: Vec2::ZERO,
             nodes,
-            assets,
             attached_node: Attach::None,
-            screen_height: height,
-            screen_width: width,
             prev_points: Vec::new(),
+            prev_pos: Vec2::ZERO,
+            mode: GameMode::Classic,
+            half_width: AREA_WIDTH / 2.0,
+            attach_reach: ATTACH_REACH,
+            max_orbit_radius: None,
+            color_rules: false,
+            one_time_nodes: false,
+            constant_angular_speed: false,
+            smooth_orbit_entry: false,
+            attach_assist: 0.0,
+            max_orbit_time: 0.0,
+            orbit_decay: 0.0,
+            orbit_time: 0.0,
+            orbit_lock: None,
+            cruise_speed: PLAYER_SPEED,
+            panic_timer: 0.0,
+            wind: 0.0,
+            thrust: 0.0,
+            attaches: 0,
+            run_time: 0.0,
+            distance: 0.0,
         })
     }
 
-    fn handle_collision(self: &Self) -> bool {
+    /// Where the player moved from and to during the last step
+    fn last_move(&self) -> (Vec2, Vec2) {
+        (self.prev_pos, self.player.pos)
+    }
+
+    /// Closest approach to any node during the last step, as a multiple of
+    /// the distance that counts as touching it
+    fn closest_node_clearance(&self) -> f32 {
+        let (from, to) = self.last_move();
+        self.nodes
+            .iter()
+            .map(|n| segment_distance(from, to, n.pos) / (self.player.bbox + n.radius))
+            .fold(f32::INFINITY, f32::min)
+    }
+
+    /// Closest approach to either wall during the last step
+    fn wall_distance(&self) -> f32 {
+        let (from, to) = self.last_move();
+        let (left, right) = (from.x.min(to.x), from.x.max(to.x));
+        let crossed = |x: f32| left <= x && x <= right;
+        if crossed(self.half_width) || crossed(-self.half_width) {
+            return 0.0;
+        }
+        let distance = |p: Vec2| (p.x.abs() - self.half_width).abs();
+        distance(from).min(distance(to))
+    }
+
+    fn handle_collision(&self) -> CollisionCause {
+        if self.mode == GameMode::Zen {
+            return CollisionCause::None;
+        }
         let is_hitting_side = match self.attached_node {
             Attach::SUCCESS(_, _) => false,
-            _ => {
-                (self.player.pos.x.abs() - (AREA_WIDTH / 2.0)).abs() < self.player.bbox
-                    && self.player.time_disconnected > 0.1
-            }
+            _ => self.wall_distance() < self.player.bbox && self.player.time_disconnected > 0.1,
         };
-        let is_hitting_node = self
-            .nodes
-            .iter()
-            .any(|n| self.player.pos.distance(n.pos) < self.player.bbox + n.radius);
+        let is_hitting_node = self.closest_node_clearance() < 1.0;
 
-        let is_outside_too_long = self.player.pos.x.abs() > AREA_WIDTH / 2.0
+        let is_outside_too_long = self.player.pos.x.abs() > self.half_width
             && self.player.time_disconnected > MAX_TIME_OUTSIDE;
 
-        return is_hitting_side || is_hitting_node || is_outside_too_long;
+        if is_hitting_side {
+            CollisionCause::Side
+        } else if is_hitting_node {
+            CollisionCause::Node
+        } else if is_outside_too_long {
+            CollisionCause::OutsideTooLong
+        } else {
+            CollisionCause::None
+        }
     }
 
-    fn handle_button_press(self: &mut Self) {
-        match self.attached_node {
-            Attach::None => {
-                match self
-                    .nodes
-                    .iter()
-                    .filter(|n| filter_deadly_nodes(&self.player, n))
-                    .min_by(|a, b| {
-                        let axp = get_cross_point(&self.player, a);
-                        let bxp = get_cross_point(&self.player, b);
-                        match axp
-                            .distance_squared(self.player.pos)
-                            .partial_cmp(&bxp.distance_squared(self.player.pos))
-                        {
-                            Some(ordering) => ordering,
-                            None => std::cmp::Ordering::Greater,
-                        }
-                    }) {
-                    Some(n) => {
-                        let delta = n.pos - self.player.pos;
-                        let angle =
-                            Vec2::from_angle(PI / 2.0 - self.player.facing).angle_between(delta);
-                        if angle.cos().abs() < 0.1 {
-                            self.attached_node =
-                                Attach::SUCCESS(*n, get_is_clockwise(&self.player, n));
-                            self.player.time_disconnected = 0.0;
-                        } else {
-                            self.attached_node =
-                                Attach::TARGET(*n, get_is_clockwise(&self.player, n));
-                        }
-                    }
-                    None => {
-                        match self
-                            .nodes
-                            .iter()
-                            .filter(|n| filter_hitting_nodes(&self.player, n))
-                            .min_by(|a, b| {
-                                a.pos
-                                    .distance_squared(self.player.pos)
-                                    .partial_cmp(&b.pos.distance_squared(self.player.pos))
-                                    .unwrap()
-                            }) {
-                            Some(n) => {
-                                self.attached_node =
-                                    Attach::SUCCESS(*n, get_is_clockwise(&self.player, n));
-                                self.player.time_disconnected = 0.0;
-                            }
-                            None => {
-                                self.attached_node = Attach::None;
-                            }
-                        }
+    /// While beyond a wall, how much of the `MAX_TIME_OUTSIDE` grace is
+    /// left, from 1.0 down to 0.0. `None` while inside or when walls can't kill.
+    fn outside_grace(&self) -> Option<f32> {
+        if self.mode == GameMode::Zen || self.player.pos.x.abs() <= self.half_width {
+            return None;
+        }
+        Some((1.0 - self.player.time_disconnected / MAX_TIME_OUTSIDE).clamp(0.0, 1.0))
+    }
+
+    /// Whether the player could start at `pos` without sitting over a wall
+    /// or being on top of a node
+    fn is_clear_spawn(&self, pos: Vec2) -> bool {
+        let bbox = self.player.bbox;
+        pos.x.abs() + bbox <= self.half_width
+            && self
+                .nodes
+                .iter()
+                .all(|n| n.pos.distance(pos) > (bbox + n.radius) * SPAWN_CLEARANCE)
+    }
+
+    /// The respawn point for a checkpoint line passed since `last`, if the
+    /// player is somewhere clear enough to respawn at
+    fn next_checkpoint(&self, last: Option<Vec2>) -> Option<Vec2> {
+        let pos = self.player.pos;
+        let passed = (pos.y / CHECKPOINT_SPACING).floor() * CHECKPOINT_SPACING;
+        let is_new = passed > last.map_or(0.0, |c| c.y);
+        (is_new && self.is_clear_spawn(pos)).then_some(pos)
+    }
+
+    /// Starts over from `pos` rather than the start of the course
+    fn respawn(&mut self, pos: Vec2) {
+        self.reset();
+        self.player.pos = pos;
+        self.prev_pos = pos;
+    }
+
+    fn has_finished(&self) -> bool {
+        self.player.pos.y >= self.finish_y
+    }
+
+    /// Whether an orbit of `radius` is within `max_orbit_radius`
+    fn fits_orbit_limit(&self, radius: f32) -> bool {
+        !matches!(self.max_orbit_radius, Some(max) if radius > max)
+    }
+
+    fn is_attachable(&self, node: &Node) -> bool {
+        !(self.color_rules && node.kind == NodeKind::Hazard)
+    }
+
+    /// Whether orbiting `node` goes clockwise, unless a direction is locked
+    fn orbit_direction(&self, node: &Node) -> bool {
+        self.orbit_lock
+            .unwrap_or_else(|| get_is_clockwise(&self.player, node))
+    }
+
+    /// Every attach a press could make, best first. Nodes the player can
+    /// swing round safely come first; failing those, the closest nodes
+    /// that aren't dead ahead.
+    fn attach_candidates(&self, steer: f32) -> Vec<Attach> {
+        let mut ahead: Vec<&Node> = self
+            .nodes
+            .iter()
+            .filter(|n| self.is_attachable(n))
+            .filter(|n| filter_deadly_nodes(&self.player, n, self.half_width, self.attach_reach))
+            // Flying straight on, the orbit is as wide as the closest the path comes to the node
+            .filter(|n| self.fits_orbit_limit(n.pos.distance(get_cross_point(&self.player, n))))
+            .collect();
+        ahead.sort_by(|a, b| {
+            let axp = get_cross_point(&self.player, a);
+            let bxp = get_cross_point(&self.player, b);
+            let a_key = axp.distance_squared(self.player.pos) - steer_bias(&self.player, a, steer);
+            let b_key = bxp.distance_squared(self.player.pos) - steer_bias(&self.player, b, steer);
+            match a_key.partial_cmp(&b_key) {
+                Some(ordering) => ordering,
+                None => std::cmp::Ordering::Greater,
+            }
+        });
+        if !ahead.is_empty() {
+            return ahead
+                .into_iter()
+                .map(|n| {
+                    let delta = n.pos - self.player.pos;
+                    let angle = direction(self.player.facing).angle_between(delta);
+                    if angle.cos().abs() < ATTACH_ALIGNMENT {
+                        Attach::SUCCESS(*n, self.orbit_direction(n))
+                    } else {
+                        Attach::TARGET(*n, self.orbit_direction(n))
                     }
-                };
+                })
+                .collect();
+        }
+        let mut fallback: Vec<&Node> = self
+            .nodes
+            .iter()
+            .filter(|n| self.is_attachable(n))
+            .filter(|n| filter_hitting_nodes(&self.player, n))
+            // These attach on the spot, orbiting at the current distance
+            .filter(|n| self.fits_orbit_limit(n.pos.distance(self.player.pos)))
+            .collect();
+        fallback.sort_by(|a, b| {
+            let a_key =
+                a.pos.distance_squared(self.player.pos) - steer_bias(&self.player, a, steer);
+            let b_key =
+                b.pos.distance_squared(self.player.pos) - steer_bias(&self.player, b, steer);
+            a_key.partial_cmp(&b_key).unwrap()
+        });
+        fallback
+            .into_iter()
+            .map(|n| Attach::SUCCESS(*n, self.orbit_direction(n)))
+            .collect()
+    }
+
+    /// What pressing the button right now would do: lock onto an aligned
+    /// node, target one to lock onto once aligned, or nothing. `steer` biases
+    /// the choice towards nodes on that side, see `steer_bias`, and `skip`
+    /// passes over that many of the best, wrapping back round past the last.
+    fn select_attach(&self, steer: f32, skip: usize) -> Attach {
+        let candidates = self.attach_candidates(steer);
+        if candidates.is_empty() {
+            return Attach::None;
+        }
+        candidates[skip % candidates.len()]
+    }
+
+    /// The node a press is waiting to line up with, or would go for if
+    /// pressed now. `None` while orbiting.
+    fn prospective_node(&self, steer: f32, skip: usize) -> Option<Node> {
+        match self.attached_node {
+            Attach::SUCCESS(..) => None,
+            Attach::TARGET(node, _) => Some(node),
+            Attach::None => match self.select_attach(steer, skip) {
+                Attach::SUCCESS(node, _) | Attach::TARGET(node, _) => Some(node),
+                Attach::None => None,
+            },
+        }
+    }
+
+    /// How close the player is to side on to `node`, from 0.0 heading
+    /// straight at or away from it to 1.0 once an attach would land
+    fn alignment(&self, node: &Node) -> f32 {
+        let delta = node.pos - self.player.pos;
+        let cos = direction(self.player.facing)
+            .angle_between(delta)
+            .cos()
+            .abs();
+        ((1.0 - cos) / (1.0 - ATTACH_ALIGNMENT)).clamp(0.0, 1.0)
+    }
+
+    /// Returns the node if the press attached to it straight away
+    fn handle_button_press(&mut self, steer: f32, skip: usize) -> Option<Node> {
+        if !matches!(self.attached_node, Attach::None) {
+            return None;
+        }
+        match self.select_attach(steer, skip) {
+            Attach::SUCCESS(node, is_clockwise) => {
+                self.attach(node, is_clockwise);
+                Some(node)
             }
-            _ => {}
-        };
+            selection => {
+                self.attached_node = selection;
+                None
+            }
+        }
     }
 
-    fn reset(self: &mut Self) {
-        self.player.pos = Vec2::new(0.0, 0.0);
-        self.player.facing = 0.0;
+    fn attach(&mut self, node: Node, is_clockwise: bool) {
+        self.attached_node = Attach::SUCCESS(node, is_clockwise);
+        // The radius the orbit would have had if the player had caught it
+        // exactly side on, rather than a little early or late
+        self.player.target_radius = self.smooth_orbit_entry.then(|| {
+            (node.pos - self.player.pos)
+                .perp_dot(direction(self.player.facing))
+                .abs()
+        });
         self.player.time_disconnected = 0.0;
+        self.orbit_time = 0.0;
+        self.attaches += 1;
+    }
+
+    /// Lets go of whatever the player is attached to
+    fn detach(&mut self) {
+        if let Attach::SUCCESS(node, _) = self.attached_node {
+            if self.one_time_nodes {
+                for n in self.nodes.iter_mut().filter(|n| n.pos == node.pos) {
+                    n.used = true;
+                }
+            }
+        }
         self.attached_node = Attach::None;
-        self.prev_points = vec![];
     }
-}
 
-impl ggez::event::EventHandler<GameError> for State {
-    fn update(&mut self, ctx: &mut Context) -> Result<(), GameError> {
-        let dt = ctx.time.delta().as_secs_f32();
+    /// Escape move: lets go of the node flying the opposite way round the
+    /// orbit, then can't be used again for `cooldown` seconds
+    fn panic_detach(&mut self, cooldown: f32) {
+        if self.panic_timer > 0.0 || !matches!(self.attached_node, Attach::SUCCESS(..)) {
+            return;
+        }
+        self.detach();
+        self.player.facing += PI;
+        self.player.velocity = self.player.speed * direction(self.player.facing);
+        self.panic_timer = cooldown;
+    }
+
+    /// Fraction of the orbit time limit still to go, if there is a limit
+    fn orbit_time_left(&self) -> Option<f32> {
+        (self.max_orbit_time > 0.0)
+            .then(|| (1.0 - self.orbit_time / self.max_orbit_time).clamp(0.0, 1.0))
+    }
+
+    /// Attach assist: turns a player who's nearly side on to the node they're
+    /// heading for the rest of the way, so the attach lands
+    fn assist_alignment(&mut self, node: &Node, dt: f32) {
+        if self.attach_assist <= 0.0 {
+            return;
+        }
+        let delta = node.pos - self.player.pos;
+        let angle = direction(self.player.facing).angle_between(delta);
+        if angle.cos().abs() >= ATTACH_ASSIST_WINDOW {
+            return;
+        }
+        // Turning anticlockwise by `error` would put the node exactly side on
+        let error = angle - (PI / 2.0).copysign(angle);
+        let max_turn = self.attach_assist * dt;
+        let turn = error.clamp(-max_turn, max_turn);
+        // Facing turns clockwise, and gravity mode steers by velocity instead
+        self.player.facing -= turn;
+        self.player.velocity = Vec2::from_angle(turn).rotate(self.player.velocity);
+    }
+
+    /// Downward acceleration in free flight, zero outside gravity mode
+    fn gravity(&self) -> f32 {
+        if self.mode == GameMode::Gravity {
+            GRAVITY
+        } else {
+            0.0
+        }
+    }
+
+    /// Where the player would be `time` seconds after letting go right now
+    fn launch_point(&self, time: f32) -> Vec2 {
+        let velocity = self.player.speed * direction(self.player.facing);
+        self.player.pos
+            + (velocity + Vec2::new(self.wind, 0.0)) * time
+            + 0.5 * Vec2::new(0.0, -self.gravity()) * time * time
+    }
+
+    /// Moves the world on by `dt`, returning the node if the player
+    /// locked onto one this step
+    fn step(&mut self, dt: f32) -> Option<Node> {
+        let mut attached = None;
+        let gravity = self.gravity();
+        self.run_time += dt;
+        self.panic_timer = (self.panic_timer - dt).max(0.0);
+        self.prev_pos = self.player.pos;
         match self.attached_node {
             Attach::SUCCESS(node, is_clockwise) => {
-                self.player.orbit(&node, dt, is_clockwise);
+                self.player.orbit(
+                    &node,
+                    dt,
+                    is_clockwise,
+                    self.constant_angular_speed,
+                    self.orbit_decay,
+                );
+                // Launch along the tangent at full speed when released
+                self.player.velocity = self.player.speed * direction(self.player.facing);
+                self.orbit_time += dt;
+                if self.max_orbit_time > 0.0 && self.orbit_time >= self.max_orbit_time {
+                    self.detach();
+                }
             }
             Attach::TARGET(node, is_clockwise) => {
+                self.assist_alignment(&node, dt);
                 let delta = node.pos - self.player.pos;
-                let angle = Vec2::from_angle(PI / 2.0 - self.player.facing).angle_between(delta);
-                if angle.cos().abs() < 0.1 {
-                    self.attached_node = Attach::SUCCESS(node, is_clockwise);
-                    self.player.time_disconnected = 0.0;
-                    self.player.orbit(&node, dt, is_clockwise);
+                let angle = direction(self.player.facing).angle_between(delta);
+                if angle.cos().abs() < ATTACH_ALIGNMENT {
+                    self.attach(node, is_clockwise);
+                    attached = Some(node);
+                    self.player.orbit(
+                        &node,
+                        dt,
+                        is_clockwise,
+                        self.constant_angular_speed,
+                        self.orbit_decay,
+                    );
                 } else {
-                    self.player.pos +=
-                        self.player.speed * dt * Vec2::from_angle(PI / 2.0 - self.player.facing);
+                    self.player.fly(dt, gravity);
                 }
             }
             Attach::None => {
-                self.player.pos +=
-                    self.player.speed * dt * Vec2::from_angle(PI / 2.0 - self.player.facing);
+                if self.thrust != 0.0 {
+                    self.player.speed = (self.player.speed
+                        + self.thrust * THRUST_ACCELERATION * dt)
+                        .clamp(MIN_THRUST_SPEED, MAX_THRUST_SPEED);
+                }
+                self.player.fly(dt, gravity);
+                self.player.pos.x += self.wind * dt;
             }
         };
         self.prev_points.push(self.player.pos);
@@ -347,152 +1464,2249 @@ impl ggez::event::EventHandler<GameError> for State {
                 self.player.time_disconnected += dt;
             }
         };
-        if self.handle_collision() {
-            self.reset();
+        if self.mode == GameMode::Zen {
+            // Walls aren't fatal in zen mode, so keep the player inside them instead
+            self.player.pos.x = self.player.pos.x.clamp(-self.half_width, self.half_width);
         }
-        Ok(())
+        self.distance += self.prev_pos.distance(self.player.pos);
+        attached
     }
 
-    fn draw(&mut self, ctx: &mut Context) -> Result<(), GameError> {
-        let coord_origin = Vec2::new(self.player.pos.x / 2.0, self.player.pos.y - 1.0);
-        let wtsc = |pos: Vec2| {
-            world_to_screen_coords(self.screen_width, self.screen_height, pos, coord_origin)
-        };
-        let mut canvas = graphics::Canvas::from_frame(ctx, graphics::Color::BLACK);
-        self.player.draw(
-            &mut self.assets,
-            &mut canvas,
-            coord_origin,
-            self.screen_width,
-            self.screen_height,
+    /// Safety net for a step that left the player somewhere non-finite,
+    /// which would otherwise silently break the camera, collision and
+    /// rendering. Logs and returns true if that happened, leaving the reset
+    /// to the caller so it can still see how the run ended.
+    fn detect_non_finite(&self) -> bool {
+        if self.player.pos.is_finite() {
+            return false;
+        }
+        warn!(
+            "Player position became {:?}, resetting the run",
+            self.player.pos
         );
-        let mb = &mut graphics::MeshBuilder::new();
-        let border_line_color = match self.attached_node {
-            Attach::SUCCESS(_, _) => graphics::Color::from_rgb(100, 100, 100),
-            _ => graphics::Color::RED,
-        };
+        true
+    }
+
+    fn reset(&mut self) {
+        self.player.pos = self.start;
+        self.prev_pos = self.player.pos;
+        self.player.facing = 0.0;
+        self.player.time_disconnected = 0.0;
+        self.player.speed = self.cruise_speed;
+        self.player.velocity = Vec2::new(0.0, self.player.speed);
+        self.attached_node = Attach::None;
+        self.panic_timer = 0.0;
+        self.prev_points = vec![];
+        self.attaches = 0;
+        self.run_time = 0.0;
+        self.distance = 0.0;
+        for n in self.nodes.iter_mut() {
+            n.used = false;
+        }
+    }
+
+    fn snapshot(&self, rng: &Rand32) -> Snapshot {
+        Snapshot {
+            version: SNAPSHOT_VERSION,
+            sim: self.clone(),
+            used: self.nodes.iter().map(|n| n.used).collect(),
+            rng: rng.state(),
+        }
+    }
+
+    fn restore(&mut self, snapshot: Snapshot, rng: &mut Rand32) {
+        *self = snapshot.sim;
+        for (node, used) in self.nodes.iter_mut().zip(snapshot.used) {
+            node.used = used;
+        }
+        *rng = Rand32::from_state(snapshot.rng);
+    }
+}
+
+fn ghost_path(data_dir: &path::Path, course_id: &str) -> path::PathBuf {
+    data_dir.join("ghosts").join(format!("{}.json", course_id))
+}
+
+/// Reads the held up and down keys as thrust, for the thrust control scheme
+fn thrust_input(ctx: &Context) -> f32 {
+    let mut thrust = 0.0;
+    if ctx.keyboard.is_key_pressed(KeyCode::Up) {
+        thrust += 1.0;
+    }
+    if ctx.keyboard.is_key_pressed(KeyCode::Down) {
+        thrust -= 1.0;
+    }
+    thrust
+}
+
+/// Reads the held arrow keys as a steering direction
+fn steer_input(ctx: &Context) -> f32 {
+    let mut steer = 0.0;
+    if ctx.keyboard.is_key_pressed(KeyCode::Left) {
+        steer -= 1.0;
+    }
+    if ctx.keyboard.is_key_pressed(KeyCode::Right) {
+        steer += 1.0;
+    }
+    steer
+}
+
+/// The player's palette if they've made one, otherwise the built-in colours
+fn load_palette(config_dir: &path::Path) -> Vec<graphics::Color> {
+    match palette::load(&config_dir.join(PALETTE_FILE)) {
+        Ok(Some(colors)) => colors,
+        Ok(None) => COLORS.to_vec(),
+        Err(e) => {
+            warn!("{}", e);
+            COLORS.to_vec()
+        }
+    }
+}
+
+/// The hand-written config file if there is one, otherwise the preset
+fn load_config(config_dir: &path::Path, difficulty: Difficulty) -> GameConfig {
+    match GameConfig::load(&config_dir.join(CONFIG_FILE)) {
+        Ok(Some(config)) => config,
+        Ok(None) => difficulty.config(),
+        Err(e) => {
+            warn!("{}", e);
+            difficulty.config()
+        }
+    }
+}
+
+fn load_settings(config_dir: &path::Path) -> Settings {
+    // A broken settings file shouldn't stop the game from starting
+    Settings::load(&config_dir.join(SETTINGS_FILE)).unwrap_or_else(|e| {
+        warn!("{}", e);
+        Settings::default()
+    })
+}
+
+fn load_best_run(data_dir: &path::Path, course_id: &str) -> Option<Run> {
+    Run::load(&ghost_path(data_dir, course_id)).unwrap_or_else(|e| {
+        warn!("{}", e);
+        None
+    })
+}
+
+impl State {
+    /// Starts on a generated course, from `seed` if given so a shared
+    /// course can be replayed, otherwise from the clock
+    fn new(ctx: &mut Context, seed: Option<u64>) -> GameResult<State> {
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .expect("Time went backwards")
+                .as_secs()
+        });
+        let mut rng = Rand32::new(seed);
+        let palette = load_palette(ctx.fs.user_config_dir());
+        let settings = load_settings(ctx.fs.user_config_dir());
+        let config = load_config(ctx.fs.user_config_dir(), settings.difficulty);
+        let nodes = make_nodes(
+            0,
+            100,
+            &mut rng,
+            &palette,
+            &config,
+            settings.color_strategy,
+            settings.lanes,
+        );
+        let mut state = State::with_nodes(
+            ctx,
+            nodes,
+            format!("seed-{}", seed),
+            palette,
+            config,
+            settings,
+        )?;
+        state.seed = Some(seed);
+        state.rng = rng;
+        info!("Starting on seed {}", seed);
+        Ok(state)
+    }
+
+    /// Starts play on a hand-authored course instead of a randomly generated one
+    fn from_level_file(ctx: &mut Context, path: &path::Path) -> GameResult<State> {
+        let level = level::load_level(path)?;
+        let name = path.file_stem().unwrap_or_default().to_string_lossy();
+        let palette = load_palette(ctx.fs.user_config_dir());
+        let settings = load_settings(ctx.fs.user_config_dir());
+        let config = load_config(ctx.fs.user_config_dir(), settings.difficulty);
+        let mut state = State::with_nodes(
+            ctx,
+            level.nodes,
+            format!("level-{}", name),
+            palette,
+            config,
+            settings,
+        )?;
+        if let Some(finish_y) = level.finish_y {
+            state.sim.finish_y = finish_y;
+        }
+        if let Some(start) = level.start {
+            state.sim.start = start;
+            state.sim.reset();
+        }
+        Ok(state)
+    }
+
+    fn with_nodes(
+        ctx: &mut Context,
+        nodes: Vec<Node>,
+        course_id: String,
+        palette: Vec<graphics::Color>,
+        config: GameConfig,
+        settings: Settings,
+    ) -> GameResult<State> {
+        // The game always lays itself out for the windowed size. Anything
+        // else, like fullscreen, gets it scaled to fit by `view_rect`.
+        let (width, height) = (SCREEN_WIDTH, SCREEN_HEIGHT);
+        let course_top = course_top(&nodes);
+        let attract = Attract::new(Sim::new(nodes.clone())?);
+        let sim = Sim::new(nodes)?;
+        let assets = Assets::new(ctx)?;
+        let config_dir = ctx.fs.user_config_dir().to_path_buf();
+        let data_dir = ctx.fs.user_data_dir().to_path_buf();
+        let best_run = load_best_run(&data_dir, &course_id);
+        // A broken save file shouldn't stop the game from starting
+        let save_data = SaveData::load(&data_dir.join(SAVE_FILE)).unwrap_or_else(|e| {
+            warn!("{}", e);
+            SaveData::default()
+        });
+        let leaderboard = Leaderboard::load(&data_dir.join(LEADERBOARD_FILE)).unwrap_or_else(|e| {
+            warn!("{}", e);
+            Leaderboard::default()
+        });
+
+        let line_scale = settings
+            .line_scale
+            .unwrap_or(ctx.gfx.window().scale_factor() as f32)
+            .clamp(0.5, 4.0);
+        // Hand-authored levels have no seed, but still need randomness for decorations
+        let mut rng = Rand32::new(0);
+        let background_nodes =
+            make_background_nodes(settings.background_nodes, course_top, &mut rng, &palette);
+        let wind_particles = (0..WIND_PARTICLES)
+            .map(|_| Vec2::new(rng.rand_float() * width, rng.rand_float() * height))
+            .collect();
+
+        let mut state = State {
+            sim,
+            attract,
+            background_nodes,
+            assets,
+            screen_height: height,
+            screen_width: width,
+            line_scale,
+            screen: Screen::Menu,
+            main_menu: Menu::new(
+                GAME_MODES
+                    .iter()
+                    .map(|(mode, _)| MenuItem::Play(*mode))
+                    .chain([MenuItem::Difficulty, MenuItem::Settings])
+                    .collect(),
+            ),
+            settings_menu: Menu::new(SETTING_ITEMS.to_vec()),
+            buffered_press_timer: 0.0,
+            steer: 0.0,
+            target_skip: 0,
+            settings,
+            config_dir,
+            data_dir,
+            course_id,
+            seed: None,
+            rng,
+            recorder: Recorder::default(),
+            best_run,
+            score: 0,
+            max_height: 0.0,
+            checkpoint: None,
+            save_data,
+            leaderboard,
+            pending_score: 0,
+            initials: String::new(),
+            recent_runs: Vec::new(),
+            ease: 0.0,
+            near_miss_cooldown: 0.0,
+            near_miss_flash: 0.0,
+            combo: 0,
+            combo_timer: 0.0,
+            debug: false,
+            frame_times: VecDeque::with_capacity(FRAME_GRAPH_LEN),
+            frame_start: Instant::now(),
+            paused: false,
+            focus_lost: false,
+            toasts: Vec::new(),
+            palette,
+            rival: None,
+            versus_wins: [0, 0],
+            hitstop_timer: 0.0,
+            attach_sound_cooldown: 0.0,
+            scrub_timer: 0.0,
+            scrubbed: false,
+            time_left: TIME_ATTACK_START,
+            intro_timer: INTRO_TIME,
+            fade_timer: 0.0,
+            death_cause: CollisionCause::None,
+            last_death: CollisionCause::None,
+            last_trail: Vec::new(),
+            breadcrumbs: Vec::new(),
+            config,
+            wind_time: 0.0,
+            wind_particles,
+            background_mesh: MeshCache::default(),
+            node_mesh: MeshCache::default(),
+        };
+        state.apply_settings();
+        Ok(state)
+    }
+
+    /// Gusts build up and die away, blowing one way and then the other.
+    /// Calm below the configured height or with wind switched off.
+    fn update_wind(&mut self, dt: f32) {
+        self.wind_time = (self.wind_time + dt) % WIND_PERIOD;
+        self.sim.wind = if self.settings.wind && self.sim.player.pos.y > self.config.wind_height {
+            WIND_STRENGTH * (self.wind_time / WIND_PERIOD * 2.0 * PI).sin().powi(3)
+        } else {
+            0.0
+        };
+        if let Some(rival) = &mut self.rival {
+            rival.wind = self.sim.wind;
+        }
+        if self.settings.performance_mode {
+            return;
+        }
+        // Specks move faster than the player drifts so the wind reads clearly
+        let drift = self.sim.wind * RATIO * 3.0 * dt;
+        for particle in self.wind_particles.iter_mut() {
+            particle.x = (particle.x + drift).rem_euclid(self.screen_width);
+        }
+    }
+
+    fn add_wind_particles(&self, mb: &mut graphics::MeshBuilder) {
+        let strength = self.sim.wind / WIND_STRENGTH;
+        for particle in &self.wind_particles {
+            let tail = *particle - Vec2::new(strength * 20.0, 0.0);
+            mb.line(
+                &[tail, *particle],
+                2.0 * self.line_scale,
+                graphics::Color::new(1.0, 1.0, 1.0, 0.5 * strength.abs()),
+            )
+            .unwrap();
+        }
+    }
+
+    fn update_near_miss(&mut self, dt: f32) {
+        self.near_miss_cooldown = (self.near_miss_cooldown - dt).max(0.0);
+        self.near_miss_flash = (self.near_miss_flash - dt).max(0.0);
+        // Orbiting close to a node is deliberate, not risky
+        if !self.settings.near_miss_feedback
+            || matches!(self.sim.attached_node, Attach::SUCCESS(_, _))
+        {
+            return;
+        }
+        let clearance = self.sim.closest_node_clearance();
+        if self.near_miss_cooldown == 0.0 && (1.0..NEAR_MISS_RATIO).contains(&clearance) {
+            self.near_miss_cooldown = NEAR_MISS_COOLDOWN;
+            self.near_miss_flash = NEAR_MISS_FLASH_TIME;
+        }
+    }
+
+    /// Nudges the hitbox and corridor width towards the player's recent
+    /// performance, staying within fixed bounds either way
+    fn update_difficulty(&mut self) {
+        self.ease = if self.settings.adaptive_difficulty {
+            difficulty_ease(&self.recent_runs, self.recorder.elapsed())
+        } else {
+            0.0
+        };
+        self.sim.player.bbox = self.config.player_bbox * (1.0 - 0.4 * self.ease);
+        // The corridor widens slightly when easing off
+        self.sim.half_width = (self.config.corridor_width / 2.0) * (1.0 + 0.05 * self.ease);
+        self.sim.attach_reach = self.config.attach_reach;
+        self.sim.max_orbit_radius = self.config.max_orbit_radius;
+    }
+
+    /// Reacts to the player locking onto `node`; every successful attach ends up here
+    fn on_attach(&mut self, ctx: &mut Context, node: &Node) -> GameResult {
+        self.target_skip = 0;
+        self.breadcrumbs.push(self.sim.player.pos);
+        if self.breadcrumbs.len() > MAX_BREADCRUMBS {
+            self.breadcrumbs.remove(0);
+        }
+        self.play_attach_sound(ctx)?;
+        let mut points = node.points();
+        if self.sim.color_rules && node.kind == NodeKind::Bonus {
+            points *= BONUS_MULTIPLIER;
+        }
+        self.score += points;
+        self.combo += 1;
+        self.combo_timer = self.settings.combo_window;
+        if self.sim.mode == GameMode::TimeAttack {
+            self.time_left += TIME_ATTACK_BONUS;
+        }
+        if self.counts_for_progress() {
+            self.save_data.total_attaches += 1;
+            self.save_data.best_combo = self.save_data.best_combo.max(self.combo);
+        }
+        Ok(())
+    }
+
+    fn play_attach_sound(&mut self, ctx: &mut Context) -> GameResult {
+        if self.attach_sound_cooldown > 0.0 {
+            return Ok(());
+        }
+        if let Some(sound) = &mut self.assets.attach_sound {
+            sound.play_detached(ctx)?;
+            self.attach_sound_cooldown = ATTACH_SOUND_COOLDOWN;
+        }
+        Ok(())
+    }
+
+    /// Whether this run should count towards stats, records and achievements
+    fn counts_for_progress(&self) -> bool {
+        self.sim.mode.tracks_progress() && !self.scrubbed
+    }
+
+    /// Unlocks any achievements earned so far, with a notification for each
+    fn check_achievements(&mut self) {
+        if !self.counts_for_progress() {
+            return;
+        }
+        let progress = Progress {
+            height: self.max_height,
+            total_attaches: self.save_data.total_attaches,
+            run_time: self.sim.run_time,
+        };
+        let unlocked: Vec<&achievements::Achievement> =
+            achievements::newly_unlocked(&progress, &self.save_data.achievements).collect();
+        if unlocked.is_empty() {
+            return;
+        }
+        for achievement in unlocked {
+            self.save_data.achievements.push(achievement.id.to_string());
+            self.toasts.push((
+                format!("Achievement unlocked: {}", achievement.name),
+                TOAST_TIME,
+            ));
+        }
+        self.save_progress();
+    }
+
+    fn save_progress(&self) {
+        if let Err(e) = self.save_data.save(&self.data_dir.join(SAVE_FILE)) {
+            error!("{}", e);
+        }
+    }
+
+    fn handle_button_press(&mut self, ctx: &mut Context) -> GameResult {
+        if let Some(node) = self.sim.handle_button_press(self.steer, self.target_skip) {
+            self.on_attach(ctx, &node)?;
+        }
+        Ok(())
+    }
+
+    fn reset(self: &mut Self) {
+        debug!("Resetting the run on {}", self.course_id);
+        self.finish_run();
+        self.last_trail = self.sim.prev_points.clone();
+        if self.settings.reseed_on_reset {
+            self.reseed();
+        }
+        match self.checkpoint {
+            Some(pos) => self.sim.respawn(pos),
+            None => self.sim.reset(),
+        }
+        self.breadcrumbs.clear();
+        if let Some(rival) = &mut self.rival {
+            rival.nodes = self.sim.nodes.clone();
+            rival.finish_y = self.sim.finish_y;
+            rival.start = self.sim.start;
+            rival.reset();
+        }
+        self.buffered_press_timer = 0.0;
+        self.target_skip = 0;
+        // Respawning at a checkpoint carries on with the run so far
+        if self.checkpoint.is_none() {
+            self.score = 0;
+            self.max_height = 0.0;
+        }
+        self.paused = false;
+        self.hitstop_timer = 0.0;
+        self.intro_timer = INTRO_TIME;
+        self.time_left = TIME_ATTACK_START;
+        self.scrub_timer = 0.0;
+        self.scrubbed = false;
+        self.near_miss_cooldown = 0.0;
+        self.near_miss_flash = 0.0;
+        self.combo = 0;
+        self.combo_timer = 0.0;
+    }
+
+    /// Runs down the combo timer, breaking the combo once it's out
+    fn update_combo(&mut self, dt: f32) {
+        if !self.settings.combo_decay || self.combo == 0 {
+            return;
+        }
+        self.combo_timer -= dt;
+        if self.combo_timer <= 0.0 {
+            self.combo = 0;
+            self.combo_timer = 0.0;
+        }
+    }
+
+    /// The current and best combos, with a bar showing how long is left to
+    /// keep the current one going
+    fn draw_combo(&self, canvas: &mut graphics::Canvas, ctx: &Context, dest: Vec2) -> GameResult {
+        canvas.draw(
+            &graphics::Text::new(format!(
+                "Combo: {}  Best: {}",
+                self.combo,
+                self.save_data.best_combo.max(self.combo)
+            )),
+            graphics::DrawParam::from(dest).color(graphics::Color::WHITE),
+        );
+        if !self.settings.combo_decay || self.combo == 0 {
+            return Ok(());
+        }
+        let left = (self.combo_timer / self.settings.combo_window).clamp(0.0, 1.0);
+        let bar = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(dest.x, dest.y + 18.0, COMBO_BAR_WIDTH * left, 4.0),
+            graphics::Color::new(1.0, 0.85, 0.0, 1.0),
+        )?;
+        canvas.draw(&bar, graphics::DrawParam::new());
+        Ok(())
+    }
+
+    /// Swaps a generated course for a fresh one. Hand-authored levels are left alone.
+    fn reseed(&mut self) {
+        if self.seed.is_none() {
+            return;
+        }
+        let seed = self.rng.rand_u32() as u64;
+        let mut rng = Rand32::new(seed);
+        // The old path and checkpoint mean nothing on a new course
+        self.last_trail.clear();
+        self.checkpoint = None;
+        self.sim.nodes = make_nodes(
+            0,
+            100,
+            &mut rng,
+            &self.palette,
+            &self.config,
+            self.settings.color_strategy,
+            self.settings.lanes,
+        );
+        self.sim.finish_y = course_top(&self.sim.nodes);
+        self.seed = Some(seed);
+        self.course_id = format!("seed-{}", seed);
+        self.best_run = load_best_run(&self.data_dir, &self.course_id);
+    }
+
+    /// Keeps the finished run as the ghost to race if it beat the best so far
+    fn finish_run(&mut self) {
+        let run_time = self.recorder.elapsed();
+        let run = self.recorder.finish();
+        let cause = std::mem::replace(&mut self.death_cause, CollisionCause::None);
+        self.last_death = cause;
+        if run_time > 0.0 {
+            self.recent_runs.push(run_time);
+            if self.recent_runs.len() > RECENT_RUNS {
+                self.recent_runs.remove(0);
+            }
+            let record = telemetry::RunRecord {
+                course_id: &self.course_id,
+                seed: self.seed,
+                mode: self.sim.mode,
+                height: self.max_height,
+                time: run_time,
+                attaches: self.sim.attaches,
+                cause,
+            };
+            if let Err(e) = telemetry::append(&self.data_dir.join(TELEMETRY_FILE), &record) {
+                error!("{}", e);
+            }
+        }
+        if !self.counts_for_progress() {
+            return;
+        }
+        self.save_data.total_distance += self.sim.distance;
+        // Ghosts and high scores only count the standard mode
+        if self.sim.mode == GameMode::Classic {
+            self.save_data.high_score = self.save_data.high_score.max(self.score);
+            self.save_data.best_height = self.save_data.best_height.max(self.max_height);
+            let best_height = self.best_run.as_ref().map_or(0.0, |r| r.height);
+            if run.height > best_height {
+                if let Err(e) = run.save(&ghost_path(&self.data_dir, &self.course_id)) {
+                    error!("{}", e);
+                }
+                self.best_run = Some(run);
+            }
+        }
+        // Saved after every run, not just records, to keep the lifetime totals
+        self.save_progress();
+    }
+
+    /// Records passing a checkpoint, in checkpoints mode
+    fn update_checkpoint(&mut self) {
+        if self.sim.mode != GameMode::Checkpoints {
+            return;
+        }
+        if let Some(pos) = self.sim.next_checkpoint(self.checkpoint) {
+            self.checkpoint = Some(pos);
+            self.toasts
+                .push(("Checkpoint reached".to_string(), TOAST_TIME));
+        }
+    }
+
+    /// Moves on from a run that died or finished, stopping for initials
+    /// first if the score made the leaderboard
+    fn end_run(&mut self) {
+        let score = self.score;
+        let qualifies = self.counts_for_progress()
+            && self.sim.mode == GameMode::Classic
+            && self.leaderboard.qualifies(score);
+        self.reset();
+        self.fade_timer = self.settings.fade_time;
+        if qualifies {
+            self.pending_score = score;
+            self.initials.clear();
+            self.change_screen(Screen::NameEntry);
+        }
+    }
+
+    /// Switches to `screen`, fading it in
+    fn change_screen(&mut self, screen: Screen) {
+        self.screen = screen;
+        self.fade_timer = self.settings.fade_time;
+    }
+
+    /// Blacks out the screen, less as the fade goes on. Drawn last, over everything.
+    fn draw_fade(&self, ctx: &Context, canvas: &mut graphics::Canvas) -> GameResult {
+        if self.fade_timer <= 0.0 || self.settings.fade_time <= 0.0 {
+            return Ok(());
+        }
+        let alpha = (self.fade_timer / self.settings.fade_time).min(1.0);
+        let quad = graphics::Mesh::new_rectangle(
+            ctx,
+            graphics::DrawMode::fill(),
+            graphics::Rect::new(0.0, 0.0, self.screen_width, self.screen_height),
+            graphics::Color::new(0.0, 0.0, 0.0, alpha),
+        )?;
+        canvas.draw(&quad, graphics::DrawParam::new());
+        Ok(())
+    }
+
+    /// Puts the pending score on the leaderboard under the initials typed so far
+    fn submit_initials(&mut self) {
+        let initials = std::mem::take(&mut self.initials);
+        self.leaderboard.insert(initials, self.pending_score);
+        if let Err(e) = self.leaderboard.save(&self.data_dir.join(LEADERBOARD_FILE)) {
+            error!("{}", e);
+        }
+    }
+
+    fn handle_name_entry_key(&mut self, keycode: KeyCode) {
+        match keycode {
+            KeyCode::Back => {
+                self.initials.pop();
+            }
+            KeyCode::Return if !self.initials.is_empty() => {
+                self.submit_initials();
+                self.change_screen(Screen::Menu);
+            }
+            KeyCode::Escape => self.change_screen(Screen::Menu),
+            _ => (),
+        }
+    }
+
+    /// Sleeps off whatever is left of the frame under the frame rate cap.
+    /// Gameplay runs on the measured frame time, so it plays the same capped or not.
+    fn limit_frame_rate(&mut self) {
+        if self.settings.frame_cap > 0 {
+            let target = Duration::from_secs_f32(1.0 / self.settings.frame_cap as f32);
+            if let Some(remaining) = target.checked_sub(self.frame_start.elapsed()) {
+                timer::sleep(remaining);
+            }
+        }
+        self.frame_start = Instant::now();
+    }
+
+    /// Save state, kept on disk so it survives a restart
+    fn save_snapshot(&mut self) {
+        let snapshot = self.sim.snapshot(&self.rng);
+        match snapshot.save(&self.data_dir.join(SNAPSHOT_FILE)) {
+            Ok(()) => self.toasts.push(("Snapshot saved".to_string(), TOAST_TIME)),
+            Err(e) => error!("{}", e),
+        }
+    }
+
+    /// Puts the course's seed on the clipboard for sharing
+    fn copy_seed(&mut self) {
+        let message = match self.seed {
+            Some(seed) => match clipboard::copy(&seed.to_string()) {
+                Ok(()) => format!("Copied seed {}", seed),
+                Err(e) => {
+                    warn!("{}", e);
+                    format!("{}, the seed is {}", e, seed)
+                }
+            },
+            None => "Hand-authored levels have no seed".to_string(),
+        };
+        self.toasts.push((message, TOAST_TIME));
+    }
+
+    fn load_snapshot(&mut self) {
+        let message = match Snapshot::load(&self.data_dir.join(SNAPSHOT_FILE)) {
+            Ok(Some(snapshot)) => {
+                self.sim.restore(snapshot, &mut self.rng);
+                // Settings may have changed since, and they win
+                self.apply_settings();
+                "Snapshot restored".to_string()
+            }
+            Ok(None) => "No snapshot saved yet".to_string(),
+            Err(e) => {
+                warn!("{}", e);
+                e.to_string()
+            }
+        };
+        self.toasts.push((message, TOAST_TIME));
+    }
+
+    fn is_paused(&self) -> bool {
+        self.paused || self.focus_lost
+    }
+
+    fn save_settings(&self) {
+        if let Err(e) = self.settings.save(&self.config_dir.join(SETTINGS_FILE)) {
+            error!("{}", e);
+        }
+    }
+
+    /// Screen coordinates that fit the game's screen into the window at
+    /// its own shape, centred with bars either side if the window's wider
+    /// or above and below if it's taller
+    fn view_rect(&self, ctx: &Context) -> graphics::Rect {
+        let (width, height) = usable_size(ctx.gfx.drawable_size());
+        let scale = (width / self.screen_width).min(height / self.screen_height);
+        let (view_width, view_height) = (width / scale, height / scale);
+        graphics::Rect::new(
+            (self.screen_width - view_width) / 2.0,
+            (self.screen_height - view_height) / 2.0,
+            view_width,
+            view_height,
+        )
+    }
+
+    fn toggle_fullscreen(&mut self, ctx: &mut Context) -> GameResult {
+        self.settings.fullscreen = !self.settings.fullscreen;
+        ctx.gfx
+            .set_fullscreen(fullscreen_type(self.settings.fullscreen))?;
+        self.save_settings();
+        Ok(())
+    }
+
+    /// How finely circles are tessellated, coarser in performance mode
+    fn circle_detail(&self) -> f32 {
+        if self.settings.performance_mode {
+            self.line_scale / PERFORMANCE_TOLERANCE
+        } else {
+            self.line_scale
+        }
+    }
+
+    /// The world position the view is drawn relative to, which sits at the
+    /// bottom middle of the screen. Keeps the player a unit up from the bottom,
+    /// and only follows them half as far sideways so the walls stay in sight.
+    fn camera_origin(&self, sim: &Sim, rival: Option<&Sim>) -> Vec2 {
+        // In versus, frame both players, keeping whoever's behind on screen
+        let focus = match rival {
+            Some(rival) => Vec2::new(
+                (sim.player.pos.x + rival.player.pos.x) / 2.0,
+                sim.player.pos.y.min(rival.player.pos.y),
+            ),
+            None => sim.player.pos,
+        };
+        // Clamped again here as the settings file may have been edited by hand
+        let lookahead = self
+            .settings
+            .camera_lookahead
+            .clamp(MIN_CAMERA_LOOKAHEAD, MAX_CAMERA_LOOKAHEAD);
+        Vec2::new(focus.x / 2.0, focus.y - lookahead)
+    }
+
+    /// The attract mode's run, dimmed behind the title menu
+    fn draw_attract(&self, ctx: &Context, canvas: &mut graphics::Canvas) -> GameResult {
+        let sim = &self.attract.sim;
+        let origin = self.camera_origin(sim, None);
+        let wtsc =
+            |pos: Vec2| world_to_screen_coords(self.screen_width, self.screen_height, pos, origin);
+        let mb = &mut graphics::MeshBuilder::new();
+        for n in &sim.nodes {
+            n.add_mesh(
+                mb,
+                origin,
+                self.screen_width,
+                self.screen_height,
+                self.circle_detail(),
+                self.settings.colorblind,
+                1.0,
+            );
+        }
+        self.add_player_trail(mb, sim, origin);
+        let dim = graphics::Color::new(1.0, 1.0, 1.0, ATTRACT_ALPHA);
+        let mesh = graphics::Mesh::from_data(ctx, mb.build());
+        canvas.draw(&mesh, graphics::DrawParam::new().color(dim));
+        let drawparams = graphics::DrawParam::new()
+            .dest(wtsc(sim.player.pos))
+            .rotation(sim.player.facing)
+            .offset(Vec2::new(0.5, 0.5))
+            .color(dim);
+        canvas.draw(&self.assets.player_image, drawparams);
+        Ok(())
+    }
+
+    /// `sim`'s trail, shortened, coloured and jittered as the trail settings ask
+    fn add_player_trail(&self, mb: &mut graphics::MeshBuilder, sim: &Sim, origin: Vec2) {
+        if !self.settings.show_trail || sim.prev_points.len() < 2 {
+            return;
+        }
+        let trail = &sim.prev_points;
+        let shown = if self.settings.performance_mode {
+            trail.len().min(PERFORMANCE_TRAIL_POINTS)
+        } else {
+            trail.len()
+        };
+        let prev_points: Vec<Vec2> = trail[trail.len() - shown..]
+            .iter()
+            .map(|p| {
+                let screen =
+                    world_to_screen_coords(self.screen_width, self.screen_height, *p, origin);
+                if self.settings.glitch_trail {
+                    screen + glitch_offset(*p, sim.run_time) * self.line_scale
+                } else {
+                    screen
+                }
+            })
+            .collect();
+        // Draw THE line!
+        if self.settings.rainbow_trail {
+            add_rainbow_trail(
+                mb,
+                &prev_points,
+                self.settings.trail_width * self.line_scale,
+                sim.run_time,
+                self.settings.colorblind,
+            );
+        } else {
+            add_trail(
+                mb,
+                &prev_points,
+                self.settings.trail_width * self.line_scale,
+                graphics::Color::WHITE,
+            );
+        }
+    }
+
+    fn draw_ghost(&self, canvas: &mut graphics::Canvas, origin: Vec2) {
+        let Some(sample) = self
+            .best_run
+            .as_ref()
+            .and_then(|run| run.sample_at(self.recorder.elapsed()))
+        else {
+            return;
+        };
+        let pos = world_to_screen_coords(self.screen_width, self.screen_height, sample.pos, origin);
+        let drawparams = graphics::DrawParam::new()
+            .dest(pos)
+            .rotation(sample.facing)
+            .offset(Vec2::new(0.5, 0.5))
+            .color(graphics::Color::new(1.0, 1.0, 1.0, 0.35));
+        canvas.draw(&self.assets.player_image, drawparams);
+    }
+
+    /// A bar under the player, `alignment` full, that turns green once an attach would land
+    fn add_alignment_meter(&self, mb: &mut graphics::MeshBuilder, alignment: f32, player: Vec2) {
+        let (width, height) = (
+            ALIGNMENT_METER_WIDTH * self.line_scale,
+            4.0 * self.line_scale,
+        );
+        let top_left = player + Vec2::new(-width / 2.0, 16.0 * self.line_scale);
+        let color = if alignment >= 1.0 {
+            graphics::Color::GREEN
+        } else {
+            graphics::Color::new(1.0, 1.0, 1.0, 0.8)
+        };
+        let outline = graphics::Rect::new(top_left.x, top_left.y, width, height);
+        let fill = graphics::Rect::new(top_left.x, top_left.y, width * alignment, height);
+        mb.rectangle(graphics::DrawMode::stroke(1.0), outline, color)
+            .and_then(|mb| mb.rectangle(graphics::DrawMode::fill(), fill, color))
+            .unwrap();
+    }
+
+    /// Brackets `node` to show it's the one a press would attach to
+    fn add_reticle(&self, mb: &mut graphics::MeshBuilder, node: &Node, origin: Vec2) {
+        let center =
+            world_to_screen_coords(self.screen_width, self.screen_height, node.pos, origin);
+        let radius = node.radius * RATIO + 6.0 * self.line_scale;
+        let tick = 6.0 * self.line_scale;
+        let color = display_color(node.color, self.settings.colorblind);
+        mb.circle(
+            graphics::DrawMode::stroke(self.line_scale),
+            center,
+            radius,
+            1.0 / self.circle_detail(),
+            color,
+        )
+        .unwrap();
+        for dir in [Vec2::X, Vec2::Y, Vec2::NEG_X, Vec2::NEG_Y] {
+            mb.line(
+                &[center + dir * radius, center + dir * (radius + tick)],
+                2.0 * self.line_scale,
+                color,
+            )
+            .unwrap();
+        }
+    }
+
+    /// The second player's tether and trail. Kept plainer than the first
+    /// player's so the two are easy to tell apart.
+    fn add_rival(&self, mb: &mut graphics::MeshBuilder, rival: &Sim, origin: Vec2) {
+        let wtsc =
+            |pos: Vec2| world_to_screen_coords(self.screen_width, self.screen_height, pos, origin);
+        let scale = self.line_scale;
+        let player_pos = wtsc(rival.player.pos);
+        match rival.attached_node {
+            Attach::SUCCESS(node, _) => {
+                let node_pos = wtsc(node.pos);
+                mb.circle(
+                    graphics::DrawMode::stroke(scale),
+                    node_pos,
+                    node_pos.distance(player_pos),
+                    1.0 / self.circle_detail(),
+                    RIVAL_COLOR,
+                )
+                .unwrap();
+                mb.line(&[node_pos, player_pos], 5.0 * scale, RIVAL_COLOR)
+                    .unwrap();
+            }
+            Attach::TARGET(node, _) => {
+                let color = display_color(node.color, self.settings.colorblind);
+                mb.line(&[wtsc(node.pos), player_pos], 5.0 * scale, color)
+                    .unwrap();
+            }
+            Attach::None => {}
+        }
+        if rival.prev_points.len() > 1 {
+            let points: Vec<Vec2> = rival.prev_points.iter().map(|p| wtsc(*p)).collect();
+            add_trail(mb, &points, self.settings.trail_width * scale, RIVAL_COLOR);
+        }
+    }
+
+    /// Faint lines fixed in the world, so climbing reads as motion even
+    /// with no nodes nearby
+    fn add_scanlines(&self, mb: &mut graphics::MeshBuilder, origin: Vec2) {
+        let first = (origin.y / SCANLINE_SPACING).floor() as i32 - 1;
+        let count = (AREA_HEIGHT / SCANLINE_SPACING).ceil() as i32 + 2;
+        for i in first..first + count {
+            let y = i as f32 * SCANLINE_SPACING;
+            let (left, right) = (origin.x - AREA_WIDTH, origin.x + AREA_WIDTH);
+            mb.line(
+                &[
+                    world_to_screen_coords(
+                        self.screen_width,
+                        self.screen_height,
+                        Vec2::new(left, y),
+                        origin,
+                    ),
+                    world_to_screen_coords(
+                        self.screen_width,
+                        self.screen_height,
+                        Vec2::new(right, y),
+                        origin,
+                    ),
+                ],
+                self.line_scale,
+                graphics::Color::new(1.0, 1.0, 1.0, 0.06),
+            )
+            .unwrap();
+        }
+    }
+
+    /// Red gradients fading in from each wall, stronger and wider the
+    /// closer the player is to that wall
+    fn add_danger_zones(&self, mb: &mut graphics::MeshBuilder, origin: Vec2) {
+        let wtsc =
+            |pos: Vec2| world_to_screen_coords(self.screen_width, self.screen_height, pos, origin);
+        let half_width = self.sim.half_width;
+        let (bottom, top) = (
+            self.sim.player.pos.y - AREA_HEIGHT,
+            self.sim.player.pos.y + AREA_HEIGHT,
+        );
+        for side in [-1.0, 1.0] {
+            let distance = half_width - side * self.sim.player.pos.x;
+            let closeness = (1.0 - distance / WALL_WARNING_DISTANCE).clamp(0.0, 1.0);
+            let width = DANGER_ZONE_WIDTH * (1.0 + closeness);
+            let alpha = 0.15 + 0.35 * closeness;
+            for i in 0..DANGER_ZONE_STRIPS {
+                let t = |i: usize| i as f32 / DANGER_ZONE_STRIPS as f32;
+                let outer = side * (half_width - width * t(i));
+                let inner = side * (half_width - width * t(i + 1));
+                mb.polygon(
+                    graphics::DrawMode::fill(),
+                    &[
+                        wtsc(Vec2::new(outer, bottom)),
+                        wtsc(Vec2::new(inner, bottom)),
+                        wtsc(Vec2::new(inner, top)),
+                        wtsc(Vec2::new(outer, top)),
+                    ],
+                    graphics::Color::new(1.0, 0.0, 0.0, alpha * (1.0 - t(i))),
+                )
+                .unwrap();
+            }
+        }
+    }
+
+    /// Flashing red border while the player is beyond a wall, flashing
+    /// faster as the grace period runs out
+    fn draw_outside_warning(&self, ctx: &Context, canvas: &mut graphics::Canvas, grace: f32) {
+        let rate = 4.0 + 8.0 * (1.0 - grace);
+        let pulse = if self.settings.performance_mode {
+            1.0
+        } else {
+            (ctx.time.time_since_start().as_secs_f32() * rate * 2.0 * PI).sin()
+        };
+        let color = graphics::Color::new(1.0, 0.0, 0.0, 0.6 + 0.4 * pulse);
+        let border = 8.0 * self.line_scale;
+        let rect = graphics::Rect::new(
+            border / 2.0,
+            border / 2.0,
+            self.screen_width - border,
+            self.screen_height - border,
+        );
+        canvas.draw(
+            &graphics::Quad,
+            graphics::DrawParam::new()
+                .dest(rect.point())
+                .scale(rect.size())
+                .color(graphics::Color::new(1.0, 0.0, 0.0, 0.1)),
+        );
+        match graphics::Mesh::new_rectangle(ctx, graphics::DrawMode::stroke(border), rect, color) {
+            Ok(mesh) => canvas.draw(&mesh, graphics::DrawParam::new()),
+            Err(e) => error!("Couldn't draw the outside warning: {}", e),
+        }
+        canvas.draw(
+            &graphics::Text::new("Get back inside!"),
+            graphics::DrawParam::from(Vec2::new(self.screen_width / 2.0 - 60.0, 120.0))
+                .color(color),
+        );
+    }
+
+    /// Thin bar up the right edge showing how far up the course the player is
+    fn add_progress_bar(&self, mb: &mut graphics::MeshBuilder) {
+        if self.sim.finish_y <= 0.0 {
+            return;
+        }
+        let progress = (self.sim.player.pos.y / self.sim.finish_y).clamp(0.0, 1.0);
+        let x = self.screen_width - 6.0 * self.line_scale;
+        let (bottom, top) = (self.screen_height - 20.0, 20.0);
+        let width = 4.0 * self.line_scale;
+        mb.line(
+            &[Vec2::new(x, bottom), Vec2::new(x, top)],
+            width,
+            graphics::Color::from_rgb(60, 60, 60),
+        )
+        .unwrap();
+        if progress > 0.0 {
+            mb.line(
+                &[
+                    Vec2::new(x, bottom),
+                    Vec2::new(x, bottom + (top - bottom) * progress),
+                ],
+                width,
+                graphics::Color::WHITE,
+            )
+            .unwrap();
+        }
+    }
+
+    /// Ends the versus round once either player is out, crediting the other
+    fn update_versus(&mut self, ctx: &mut Context) -> GameResult {
+        let Some(rival) = &self.rival else {
+            return Ok(());
+        };
+        let lead = self.sim.player.pos.y - rival.player.pos.y;
+        self.death_cause = self.sim.handle_collision();
+        // Crossing the finish line first knocks the other player out
+        let first_out = self.death_cause != CollisionCause::None
+            || lead < -VERSUS_MAX_GAP
+            || rival.has_finished();
+        let second_out = rival.handle_collision() != CollisionCause::None
+            || lead > VERSUS_MAX_GAP
+            || self.sim.has_finished();
+        let message = match (first_out, second_out) {
+            (false, false) => return Ok(()),
+            (true, true) => "Draw!",
+            (false, true) => {
+                self.versus_wins[0] += 1;
+                "Player 1 wins the round"
+            }
+            (true, false) => {
+                self.versus_wins[1] += 1;
+                "Player 2 wins the round"
+            }
+        };
+        self.toasts.push((message.to_string(), TOAST_TIME));
+        self.assets.hit_sound.play_detached(ctx)?;
+        self.hitstop_timer = HITSTOP_TIME;
+        Ok(())
+    }
+
+    /// Debug overlay: the velocity actually flown over the last step in
+    /// green, and the speed along `facing` in grey. Where they disagree is
+    /// where the attach transitions snap.
+    fn add_velocity_arrows(&self, mb: &mut graphics::MeshBuilder, origin: Vec2, dt: f32) {
+        let wtsc =
+            |pos: Vec2| world_to_screen_coords(self.screen_width, self.screen_height, pos, origin);
+        let (from, to) = self.sim.last_move();
+        let measured = if dt > 0.0 {
+            (to - from) / dt
+        } else {
+            Vec2::ZERO
+        };
+        let facing = self.sim.player.speed * direction(self.sim.player.facing);
+        let scale = self.line_scale;
+        for (velocity, color) in [
+            (facing, graphics::Color::from_rgb(150, 150, 150)),
+            (measured, graphics::Color::GREEN),
+        ] {
+            let tip = to + velocity * VELOCITY_ARROW_TIME;
+            if tip.distance(to) < 0.01 {
+                continue;
+            }
+            mb.line(&[wtsc(to), wtsc(tip)], 2.0 * scale, color).unwrap();
+            let back = -velocity.normalize() * 0.08;
+            for side in [PI / 6.0, -PI / 6.0] {
+                let barb = tip + Vec2::from_angle(side).rotate(back);
+                mb.line(&[wtsc(tip), wtsc(barb)], 2.0 * scale, color)
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Debug overlay plotting recent frame times in the bottom-right corner.
+    /// The grey line marks 60fps and the graph tops out at 30fps.
+    fn add_frame_graph(&self, mb: &mut graphics::MeshBuilder) {
+        let (width, height) = (FRAME_GRAPH_LEN as f32, 50.0);
+        let corner = Vec2::new(self.screen_width - width - 10.0, self.screen_height - 10.0);
+        let to_y = |dt: f32| corner.y - (dt * 30.0).min(1.0) * height;
+        mb.line(
+            &[
+                Vec2::new(corner.x, to_y(1.0 / 60.0)),
+                Vec2::new(corner.x + width, to_y(1.0 / 60.0)),
+            ],
+            self.line_scale,
+            graphics::Color::from_rgb(100, 100, 100),
+        )
+        .unwrap();
+        if self.frame_times.len() > 1 {
+            let points: Vec<Vec2> = self
+                .frame_times
+                .iter()
+                .enumerate()
+                .map(|(i, dt)| Vec2::new(corner.x + i as f32, to_y(*dt)))
+                .collect();
+            mb.line(&points, self.line_scale, graphics::Color::GREEN)
+                .unwrap();
+        }
+    }
+
+    /// Retries a recent press that didn't find a node, so pressing
+    /// slightly too early still attaches once a node becomes reachable
+    fn update_buffered_press(&mut self, ctx: &mut Context, dt: f32) -> GameResult {
+        if self.buffered_press_timer <= 0.0 {
+            return Ok(());
+        }
+        if let Attach::None = self.sim.attached_node {
+            self.handle_button_press(ctx)?;
+        }
+        if let Attach::None = self.sim.attached_node {
+            self.buffered_press_timer = (self.buffered_press_timer - dt).max(0.0);
+        } else {
+            self.buffered_press_timer = 0.0;
+        }
+        Ok(())
+    }
+
+    /// Pushes changed settings out to everything that depends on them
+    fn apply_settings(&mut self) {
+        self.sim.color_rules = self.settings.color_rules;
+        self.sim.one_time_nodes = self.settings.one_time_nodes;
+        self.sim.constant_angular_speed = self.settings.constant_angular_speed;
+        self.sim.smooth_orbit_entry = self.settings.smooth_orbit_entry;
+        self.sim.attach_assist = self.settings.attach_assist * ATTACH_ASSIST_TURN;
+        self.sim.max_orbit_time = self.settings.max_orbit_time;
+        self.sim.orbit_decay = self.settings.orbit_decay;
+        self.sim.orbit_lock = self.settings.orbit_direction.lock();
+        self.sim.cruise_speed = self.config.player_speed;
+        if let Some(rival) = &mut self.rival {
+            rival.cruise_speed = self.config.player_speed;
+            rival.color_rules = self.settings.color_rules;
+            rival.one_time_nodes = self.settings.one_time_nodes;
+            rival.constant_angular_speed = self.settings.constant_angular_speed;
+            rival.smooth_orbit_entry = self.settings.smooth_orbit_entry;
+            rival.attach_assist = self.settings.attach_assist * ATTACH_ASSIST_TURN;
+            rival.max_orbit_time = self.settings.max_orbit_time;
+            rival.orbit_decay = self.settings.orbit_decay;
+            rival.orbit_lock = self.settings.orbit_direction.lock();
+        }
+        self.assets.hit_sound.set_volume(self.settings.volume);
+        if let Some(sound) = &mut self.assets.attach_sound {
+            sound.set_volume(self.settings.volume);
+        }
+    }
+
+    fn draw_menu_items(
+        &self,
+        canvas: &mut graphics::Canvas,
+        title: &str,
+        items: &[String],
+        selected: usize,
+    ) {
+        canvas.draw(
+            &graphics::Text::new(title),
+            graphics::DrawParam::from(Vec2::new(10.0, 10.0)).color(graphics::Color::WHITE),
+        );
+        let fits = ((self.screen_height - MENU_TOP) / MENU_ROW_HEIGHT) as usize;
+        let shown = menu::visible_rows(items.len(), selected, fits);
+        for (row, i) in shown.enumerate() {
+            let (label, color) = if i == selected {
+                (format!("> {}", items[i]), graphics::Color::YELLOW)
+            } else {
+                (format!("  {}", items[i]), graphics::Color::WHITE)
+            };
+            let y = MENU_TOP + MENU_ROW_HEIGHT * row as f32;
+            canvas.draw(
+                &graphics::Text::new(label),
+                graphics::DrawParam::from(Vec2::new(10.0, y)).color(color),
+            );
+        }
+    }
+
+    /// The game modes, followed by a link to the settings screen
+    fn draw_menu(&self, canvas: &mut graphics::Canvas) {
+        let items: Vec<String> = self
+            .main_menu
+            .items()
+            .iter()
+            .map(|item| match item {
+                MenuItem::Play(mode) => GAME_MODES
+                    .iter()
+                    .find(|(m, _)| m == mode)
+                    .map_or("", |(_, name)| name)
+                    .to_string(),
+                MenuItem::Difficulty => {
+                    format!("Difficulty: < {} >", self.settings.difficulty.name())
+                }
+                MenuItem::Settings => "Settings".to_string(),
+            })
+            .collect();
+        self.draw_menu_items(
+            canvas,
+            "One More Line",
+            &items,
+            self.main_menu.selected_index(),
+        );
+
+        let unlocked = |id: &str| self.save_data.achievements.iter().any(|a| a == id);
+        let top = 70.0 + 20.0 * items.len() as f32;
+        let count = ACHIEVEMENTS.iter().filter(|a| unlocked(a.id)).count();
+        canvas.draw(
+            &graphics::Text::new(format!("Achievements {}/{}", count, ACHIEVEMENTS.len())),
+            graphics::DrawParam::from(Vec2::new(10.0, top)).color(graphics::Color::WHITE),
+        );
+        for (i, achievement) in ACHIEVEMENTS.iter().enumerate() {
+            let color = if unlocked(achievement.id) {
+                graphics::Color::new(1.0, 0.85, 0.0, 1.0)
+            } else {
+                graphics::Color::from_rgb(100, 100, 100)
+            };
+            canvas.draw(
+                &graphics::Text::new(format!(
+                    "  {} - {}",
+                    achievement.name, achievement.description
+                )),
+                graphics::DrawParam::from(Vec2::new(10.0, top + 20.0 * (i + 1) as f32))
+                    .color(color),
+            );
+        }
+        let top = top + 20.0 * (ACHIEVEMENTS.len() + 2) as f32;
+        self.draw_leaderboard(canvas, 10.0, top);
+    }
+
+    /// The best scores, top down from `top`
+    fn draw_leaderboard(&self, canvas: &mut graphics::Canvas, x: f32, top: f32) {
+        canvas.draw(
+            &graphics::Text::new("High scores"),
+            graphics::DrawParam::from(Vec2::new(x, top)).color(graphics::Color::WHITE),
+        );
+        if self.leaderboard.entries.is_empty() {
+            canvas.draw(
+                &graphics::Text::new("  None yet"),
+                graphics::DrawParam::from(Vec2::new(x, top + 20.0))
+                    .color(graphics::Color::from_rgb(100, 100, 100)),
+            );
+        }
+        for (i, entry) in self.leaderboard.entries.iter().enumerate() {
+            canvas.draw(
+                &graphics::Text::new(format!(
+                    "{:>2}. {:<3} {:>6}",
+                    i + 1,
+                    entry.initials,
+                    entry.score
+                )),
+                graphics::DrawParam::from(Vec2::new(x, top + 20.0 * (i + 1) as f32))
+                    .color(graphics::Color::WHITE),
+            );
+        }
+    }
+
+    fn draw_name_entry(&self, canvas: &mut graphics::Canvas) {
+        let typed: String = self
+            .initials
+            .chars()
+            .chain(std::iter::repeat('_'))
+            .take(INITIALS_LEN)
+            .collect();
+        let lines = [
+            match self.last_death.message() {
+                Some(message) => format!("{} - new high score: {}", message, self.pending_score),
+                None => format!("New high score: {}", self.pending_score),
+            },
+            format!("Enter your initials: {}", typed),
+            "Return to save, Escape to skip".to_string(),
+        ];
+        for (i, line) in lines.iter().enumerate() {
+            canvas.draw(
+                &graphics::Text::new(line.as_str()),
+                graphics::DrawParam::from(Vec2::new(10.0, 10.0 + 30.0 * i as f32))
+                    .color(graphics::Color::YELLOW),
+            );
+        }
+        self.draw_leaderboard(canvas, 10.0, 120.0);
+    }
+
+    fn draw_settings(&self, canvas: &mut graphics::Canvas) {
+        let items: Vec<String> = self
+            .settings_menu
+            .items()
+            .iter()
+            .map(|item| self.settings.label(*item))
+            .collect();
+        self.draw_menu_items(
+            canvas,
+            "Settings",
+            &items,
+            self.settings_menu.selected_index(),
+        );
+    }
+
+    /// Switches preset and builds a fresh course to suit it. The config
+    /// file, if there is one, still wins over any preset.
+    fn change_difficulty(&mut self, step: i32) {
+        self.settings.difficulty = self.settings.difficulty.cycle(step);
+        self.save_settings();
+        self.config = load_config(&self.config_dir, self.settings.difficulty);
+        self.apply_settings();
+        self.reseed();
+    }
+
+    fn handle_menu_key(&mut self, ctx: &mut Context, keycode: KeyCode) -> GameResult {
+        let item = self.main_menu.select();
+        match (keycode, item) {
+            (KeyCode::Up, _) => self.main_menu.move_up(),
+            (KeyCode::Down, _) => self.main_menu.move_down(),
+            (KeyCode::Left, MenuItem::Difficulty) => self.change_difficulty(-1),
+            (KeyCode::Right | KeyCode::Space | KeyCode::Return, MenuItem::Difficulty) => {
+                self.change_difficulty(1)
+            }
+            (KeyCode::Space | KeyCode::Return, MenuItem::Settings) => {
+                self.settings_menu.highlight(SETTING_ITEMS[0]);
+                self.change_screen(Screen::Settings);
+            }
+            (KeyCode::Space | KeyCode::Return, MenuItem::Play(mode)) => {
+                self.checkpoint = None;
+                self.reset();
+                self.sim.mode = mode;
+                self.rival = None;
+                if mode == GameMode::Versus {
+                    let mut rival = Sim::new(self.sim.nodes.clone())?;
+                    rival.mode = mode;
+                    rival.finish_y = self.sim.finish_y;
+                    rival.start = self.sim.start;
+                    rival.cruise_speed = self.sim.cruise_speed;
+                    rival.reset();
+                    self.rival = Some(rival);
+                    self.versus_wins = [0, 0];
+                    self.apply_settings();
+                }
+                self.change_screen(Screen::Playing);
+            }
+            (KeyCode::Escape, _) => ctx.request_quit(),
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn handle_settings_key(&mut self, keycode: KeyCode) {
+        let item = self.settings_menu.select();
+        match keycode {
+            KeyCode::Up => self.settings_menu.move_up(),
+            KeyCode::Down => self.settings_menu.move_down(),
+            KeyCode::Left => self.settings.adjust(item, -1),
+            KeyCode::Right => self.settings.adjust(item, 1),
+            KeyCode::Space | KeyCode::Return if item != SettingItem::Back => {
+                self.settings.adjust(item, 1);
+            }
+            KeyCode::Space | KeyCode::Return | KeyCode::Escape => {
+                self.save_settings();
+                self.main_menu.highlight(MenuItem::Settings);
+                self.change_screen(Screen::Menu);
+            }
+            _ => (),
+        }
+        self.apply_settings();
+    }
+
+    /// Advances the run by `dt`, one sim step along with everything that
+    /// reacts to it
+    fn tick(&mut self, ctx: &mut Context, dt: f32) -> GameResult {
+        if self.hitstop_timer > 0.0 {
+            // Hold on the moment of impact, then carry on with the death
+            self.hitstop_timer -= dt;
+            if self.hitstop_timer <= 0.0 {
+                self.end_run();
+            }
+            return Ok(());
+        }
+        self.steer = steer_input(ctx);
+        self.sim.thrust = if self.settings.thrust_control {
+            thrust_input(ctx)
+        } else {
+            0.0
+        };
+        self.update_difficulty();
+        self.update_wind(dt);
+        self.attach_sound_cooldown = (self.attach_sound_cooldown - dt).max(0.0);
+        self.update_combo(dt);
+        self.update_buffered_press(ctx, dt)?;
+        if let Some(node) = self.sim.step(dt) {
+            self.on_attach(ctx, &node)?;
+        }
+        if self.sim.detect_non_finite() {
+            self.reset();
+            return Ok(());
+        }
+        self.max_height = self.max_height.max(self.sim.player.pos.y);
+        self.update_checkpoint();
+        self.check_achievements();
+        for toast in self.toasts.iter_mut() {
+            toast.1 -= dt;
+        }
+        self.toasts.retain(|(_, time)| *time > 0.0);
+        self.intro_timer = (self.intro_timer - dt).max(0.0);
+        self.scrub_timer = (self.scrub_timer - dt).max(0.0);
+        self.recorder
+            .record(dt, self.sim.player.pos, self.sim.player.facing);
+        if let Some(rival) = &mut self.rival {
+            rival.half_width = self.sim.half_width;
+            rival.attach_reach = self.sim.attach_reach;
+            rival.max_orbit_radius = self.sim.max_orbit_radius;
+            rival.player.bbox = self.sim.player.bbox;
+            let attached = rival.step(dt).is_some();
+            if rival.detect_non_finite() {
+                self.reset();
+                return Ok(());
+            }
+            if attached {
+                self.play_attach_sound(ctx)?;
+            }
+            if self.intro_timer > 0.0 {
+                return Ok(());
+            }
+            return self.update_versus(ctx);
+        }
+        if self.intro_timer > 0.0 || self.scrub_timer > 0.0 {
+            return Ok(());
+        }
+        if self.sim.has_finished() {
+            self.toasts
+                .push(("Course complete!".to_string(), TOAST_TIME));
+            self.checkpoint = None;
+            self.end_run();
+            return Ok(());
+        }
+        let mut cause = self.sim.handle_collision();
+        if cause == CollisionCause::None && self.sim.mode == GameMode::TimeAttack {
+            self.time_left -= dt;
+            if self.time_left <= 0.0 {
+                self.time_left = 0.0;
+                self.toasts.push(("Out of time!".to_string(), TOAST_TIME));
+                cause = CollisionCause::TimeUp;
+            }
+        }
+        match cause {
+            CollisionCause::None => self.update_near_miss(dt),
+            cause => {
+                info!(
+                    "Run ended: {:?} at {:?}, height {:.1}",
+                    cause, self.sim.player.pos, self.max_height
+                );
+                self.death_cause = cause;
+                self.assets.hit_sound.play_detached(ctx)?;
+                self.hitstop_timer = HITSTOP_TIME;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl ggez::event::EventHandler<GameError> for State {
+    fn update(&mut self, ctx: &mut Context) -> Result<(), GameError> {
+        self.limit_frame_rate();
+        if self.frame_times.len() == FRAME_GRAPH_LEN {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(ctx.time.delta().as_secs_f32());
+        self.fade_timer = (self.fade_timer - ctx.time.delta().as_secs_f32()).max(0.0);
+        if self.screen == Screen::Menu {
+            self.attract
+                .update(frame_time(ctx.time.delta().as_secs_f32()));
+        }
+        if self.screen != Screen::Playing || self.is_paused() {
+            return Ok(());
+        }
+        let dt = frame_time(ctx.time.delta().as_secs_f32());
+        // Fast-forward takes more steps rather than longer ones, so collision
+        // sees everything it would at normal speed
+        let steps = if self.debug && ctx.keyboard.is_key_pressed(KeyCode::F) {
+            FAST_FORWARD_STEPS
+        } else {
+            1
+        };
+        for _ in 0..steps {
+            self.tick(ctx, dt)?;
+            if self.screen != Screen::Playing {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn draw(&mut self, ctx: &mut Context) -> Result<(), GameError> {
+        let coord_origin = self.camera_origin(&self.sim, self.rival.as_ref());
+        let wtsc = |pos: Vec2| {
+            world_to_screen_coords(self.screen_width, self.screen_height, pos, coord_origin)
+        };
+        let background = if self.screen == Screen::Playing && !self.settings.classic_background {
+            background_color(self.sim.player.pos.y)
+        } else {
+            graphics::Color::BLACK
+        };
+        let mut canvas = graphics::Canvas::from_frame(ctx, background);
+        let screen_rect = self.view_rect(ctx);
+        canvas.set_screen_coordinates(screen_rect);
+        match self.screen {
+            Screen::Menu => {
+                self.draw_attract(ctx, &mut canvas)?;
+                self.draw_menu(&mut canvas);
+            }
+            Screen::Settings => self.draw_settings(&mut canvas),
+            Screen::NameEntry => self.draw_name_entry(&mut canvas),
+            Screen::Playing => {}
+        }
+        if self.screen != Screen::Playing {
+            self.draw_fade(ctx, &mut canvas)?;
+            canvas.finish(ctx)?;
+            return Ok(());
+        }
+        if self.settings.invert_vertical {
+            // Mirror the playfield top to bottom so the player descends.
+            // The sim itself still climbs, so collision and scoring are untouched.
+            canvas.set_screen_coordinates(graphics::Rect::new(
+                screen_rect.x,
+                screen_rect.y + screen_rect.h,
+                screen_rect.w,
+                -screen_rect.h,
+            ));
+        }
+        let effects = !self.settings.performance_mode;
+        if effects && self.settings.scanlines {
+            let scanline_mb = &mut graphics::MeshBuilder::new();
+            self.add_scanlines(scanline_mb, coord_origin);
+            let scanlines = graphics::Mesh::from_data(ctx, scanline_mb.build());
+            canvas.draw(&scanlines, graphics::DrawParam::new());
+        }
+        // Where the world origin lands on screen, for shifting the cached node meshes
+        let origin_screen = world_to_screen_coords(
+            self.screen_width,
+            self.screen_height,
+            Vec2::ZERO,
+            Vec2::ZERO,
+        );
+        let detail = self.circle_detail();
+        let display_key = [
+            self.screen_width,
+            self.screen_height,
+            detail,
+            self.settings.colorblind as u8 as f32,
+        ];
+        let key = node_mesh_key(&self.background_nodes, &display_key);
+        let background_mesh = self.background_mesh.get(ctx, key, |mb| {
+            if !effects {
+                return;
+            }
+            for n in &self.background_nodes {
+                n.add_mesh(
+                    mb,
+                    Vec2::ZERO,
+                    self.screen_width,
+                    self.screen_height,
+                    detail,
+                    self.settings.colorblind,
+                    1.0,
+                );
+            }
+        });
+        if let Some(mesh) = background_mesh {
+            let offset = world_to_screen_coords(
+                self.screen_width,
+                self.screen_height,
+                Vec2::ZERO,
+                coord_origin * BACKGROUND_PARALLAX,
+            ) - origin_screen;
+            canvas.draw(mesh, graphics::DrawParam::new().dest(offset));
+        }
+        if self.settings.show_ghost {
+            self.draw_ghost(&mut canvas, coord_origin);
+        }
+        if self.settings.smooth_player {
+            canvas.set_sampler(graphics::Sampler::linear_clamp());
+        } else {
+            canvas.set_sampler(graphics::Sampler::nearest_clamp());
+        }
+        self.sim.player.draw(
+            &mut self.assets,
+            &mut canvas,
+            coord_origin,
+            self.screen_width,
+            self.screen_height,
+            graphics::Color::WHITE,
+        );
+        if let Some(rival) = &self.rival {
+            rival.player.draw(
+                &mut self.assets,
+                &mut canvas,
+                coord_origin,
+                self.screen_width,
+                self.screen_height,
+                RIVAL_COLOR,
+            );
+        }
+        canvas.set_default_sampler();
+        let mb = &mut graphics::MeshBuilder::new();
+        let scale = self.line_scale;
+        if self.settings.show_last_trail && self.last_trail.len() > 1 {
+            // The previous run's path, with a cross where it ended
+            let color = graphics::Color::new(1.0, 1.0, 1.0, 0.2);
+            let points: Vec<Vec2> = self.last_trail.iter().map(|p| wtsc(*p)).collect();
+            add_trail(mb, &points, 3.0 * scale, color);
+            let end = points[points.len() - 1];
+            let arm = 8.0 * scale;
+            for corner in [Vec2::new(arm, arm), Vec2::new(arm, -arm)] {
+                mb.line(&[end - corner, end + corner], 3.0 * scale, color)
+                    .unwrap();
+            }
+        }
+        let border_line_color = match self.sim.attached_node {
+            Attach::SUCCESS(_, _) => graphics::Color::from_rgb(100, 100, 100),
+            _ => graphics::Color::RED,
+        };
 
-        mb.line(
-            &vec![
-                wtsc(Vec2::new(
-                    -AREA_WIDTH / 2.0,
-                    self.player.pos.y - AREA_HEIGHT,
-                )),
-                wtsc(Vec2::new(
-                    -AREA_WIDTH / 2.0,
-                    self.player.pos.y + AREA_HEIGHT,
-                )),
-            ],
-            5.0,
-            border_line_color,
-        )
-        .unwrap();
+        let half_width = self.sim.half_width;
+        if self.settings.danger_zone {
+            self.add_danger_zones(mb, coord_origin);
+        }
+        for side in [-1.0, 1.0] {
+            let x = side * half_width;
+            // 0.0 while well clear of this wall, rising to 1.0 on touching it
+            let glow = if self.settings.wall_warning {
+                let distance = half_width - side * self.sim.player.pos.x;
+                (1.0 - distance / WALL_WARNING_DISTANCE).clamp(0.0, 1.0)
+            } else {
+                0.0
+            };
+            let color = graphics::Color::new(
+                border_line_color.r + (1.0 - border_line_color.r) * glow,
+                border_line_color.g + (1.0 - border_line_color.g) * glow,
+                border_line_color.b + (1.0 - border_line_color.b) * glow,
+                1.0,
+            );
+            mb.line(
+                &[
+                    wtsc(Vec2::new(x, self.sim.player.pos.y - AREA_HEIGHT)),
+                    wtsc(Vec2::new(x, self.sim.player.pos.y + AREA_HEIGHT)),
+                ],
+                self.settings.border_width * (1.0 + glow) * scale,
+                color,
+            )
+            .unwrap();
+        }
+
+        if self.sim.mode == GameMode::Checkpoints {
+            let lowest = ((self.sim.player.pos.y - AREA_HEIGHT) / CHECKPOINT_SPACING).ceil();
+            let highest = ((self.sim.player.pos.y + AREA_HEIGHT) / CHECKPOINT_SPACING).floor();
+            for i in (lowest.max(1.0) as i32)..=(highest as i32) {
+                let y = i as f32 * CHECKPOINT_SPACING;
+                let alpha = if self.checkpoint.is_some_and(|c| c.y >= y) {
+                    0.8
+                } else {
+                    0.35
+                };
+                mb.line(
+                    &[
+                        wtsc(Vec2::new(-half_width, y)),
+                        wtsc(Vec2::new(half_width, y)),
+                    ],
+                    3.0 * scale,
+                    graphics::Color::new(0.2, 1.0, 0.4, alpha),
+                )
+                .unwrap();
+            }
+        }
 
+        // Start line at the bottom, chequered finish line at the top
+        let start_y = self.sim.start.y;
         mb.line(
-            &vec![
-                wtsc(Vec2::new(AREA_WIDTH / 2.0, self.player.pos.y - AREA_HEIGHT)),
-                wtsc(Vec2::new(AREA_WIDTH / 2.0, self.player.pos.y + AREA_HEIGHT)),
+            &[
+                wtsc(Vec2::new(-half_width, start_y)),
+                wtsc(Vec2::new(half_width, start_y)),
             ],
-            5.0,
-            border_line_color,
+            3.0 * scale,
+            graphics::Color::from_rgb(100, 100, 100),
         )
         .unwrap();
+        let squares = 12;
+        for i in 0..squares {
+            let x = |i: usize| -half_width + 2.0 * half_width * i as f32 / squares as f32;
+            let color = if i % 2 == 0 {
+                graphics::Color::WHITE
+            } else {
+                graphics::Color::from_rgb(60, 60, 60)
+            };
+            mb.line(
+                &[
+                    wtsc(Vec2::new(x(i), self.sim.finish_y)),
+                    wtsc(Vec2::new(x(i + 1), self.sim.finish_y)),
+                ],
+                6.0 * scale,
+                color,
+            )
+            .unwrap();
+        }
+
+        // Everything so far sits under the nodes, and everything after on top
+        let mesh = graphics::Mesh::from_data(ctx, mb.build());
+        canvas.draw(&mesh, graphics::DrawParam::new());
+        let reveal = 1.0 - self.intro_timer / INTRO_TIME;
+        let node_image = self
+            .assets
+            .node_image
+            .as_ref()
+            .filter(|_| self.settings.node_sprites);
+        if let Some(image) = node_image {
+            for n in &self.sim.nodes {
+                n.draw_sprite(
+                    &mut canvas,
+                    image,
+                    coord_origin,
+                    self.screen_width,
+                    self.screen_height,
+                    self.settings.colorblind,
+                    reveal,
+                );
+            }
+        } else {
+            // Rebuilt every frame of the intro as the nodes grow, then left alone
+            let key = node_mesh_key(&self.sim.nodes, &[&display_key[..], &[reveal]].concat());
+            let node_mesh = self.node_mesh.get(ctx, key, |mb| {
+                for n in &self.sim.nodes {
+                    n.add_mesh(
+                        mb,
+                        Vec2::ZERO,
+                        self.screen_width,
+                        self.screen_height,
+                        detail,
+                        self.settings.colorblind,
+                        reveal,
+                    );
+                }
+            });
+            if let Some(mesh) = node_mesh {
+                let offset = wtsc(Vec2::ZERO) - origin_screen;
+                canvas.draw(mesh, graphics::DrawParam::new().dest(offset));
+            }
+        }
+        let mb = &mut graphics::MeshBuilder::new();
+        // // Uncomment this block to show valid node lines
+        // for n in &self.sim.nodes {
+        //     if filter_deadly_nodes(&self.sim.player, n, self.sim.half_width, self.sim.attach_reach) {
+        //         mb.line(
+        //             &[
+        //                 wtsc(self.sim.player.pos),
+        //                 wtsc(get_cross_point(&self.sim.player, n)),
+        //                 wtsc(n.pos),
+        //             ],
+        //             1.0,
+        //             n.color,
+        //         )
+        //         .unwrap();
+        //     }
+        // }
 
-        for n in &self.nodes {
-            n.add_mesh(mb, coord_origin, self.screen_width, self.screen_height);
-            // // Uncomment this block to show valid node lines
-            // if filter_deadly_nodes(&self.player, n) {
-            //     mb.line(
-            //         &[
-            //             wtsc(self.player.pos),
-            //             wtsc(get_cross_point(&self.player, n)),
-            //             wtsc(n.pos),
-            //         ],
-            //         1.0,
-            //         n.color,
-            //     )
-            //     .unwrap();
-            // }
-        }
-
-        let add_line = |mb: &mut graphics::MeshBuilder, node: &Node| {
+        let add_line = |mb: &mut graphics::MeshBuilder, node: &Node, is_clockwise: bool| {
             let node_pos = wtsc(node.pos);
-            let player_pos = wtsc(self.player.pos);
+            let player_pos = wtsc(self.sim.player.pos);
             let radius = node_pos.distance(player_pos);
             mb.circle(
-                graphics::DrawMode::Stroke(graphics::StrokeOptions::DEFAULT),
+                graphics::DrawMode::stroke(scale),
                 node_pos,
                 radius,
-                1.0,
+                1.0 / detail,
                 graphics::Color::WHITE,
             )
             .unwrap();
-            mb.line(&[node_pos, player_pos], 5.0, graphics::Color::WHITE)
+            // Bow the tether back against the swing, as if it had some give.
+            // Only the drawing bends; the orbit itself stays rigid.
+            let mid = (node.pos + self.sim.player.pos) / 2.0;
+            let control =
+                mid - direction(self.sim.player.facing) * self.sim.player.speed * TETHER_BOW;
+            let tether: Vec<Vec2> = (0..=16)
+                .map(|i| {
+                    let t = i as f32 / 16.0;
+                    let point = (1.0 - t) * (1.0 - t) * node.pos
+                        + 2.0 * (1.0 - t) * t * control
+                        + t * t * self.sim.player.pos;
+                    wtsc(point)
+                })
+                .collect();
+            mb.line(&tether, 5.0 * scale, graphics::Color::WHITE)
                 .unwrap();
+            // Arc over the next stretch of the orbit, so faster swings show a longer arc
+            let delta = self.sim.player.pos - node.pos;
+            let world_radius = delta.length();
+            if world_radius > 0.0 {
+                let angular_speed = self
+                    .sim
+                    .player
+                    .angular_speed(world_radius, self.sim.constant_angular_speed);
+                let sweep = (angular_speed * ORBIT_PREVIEW_TIME).min(2.0 * PI);
+                let sweep = if is_clockwise { -sweep } else { sweep };
+                let arc: Vec<Vec2> = (0..=16)
+                    .map(|i| {
+                        let angle = sweep * i as f32 / 16.0;
+                        wtsc(node.pos + Vec2::from_angle(angle).rotate(delta))
+                    })
+                    .collect();
+                mb.line(&arc, 3.0 * scale, graphics::Color::YELLOW).unwrap();
+            }
+            // Ring round the node that shrinks away as the orbit time runs out
+            if let Some(left) = self.sim.orbit_time_left().filter(|&left| left > 0.01) {
+                let ring_radius = node.radius * RATIO + 8.0 * scale;
+                let segments = ((32.0 * left).ceil() as usize).max(2);
+                let ring: Vec<Vec2> = (0..=segments)
+                    .map(|i| {
+                        // Screen y points down, so this starts at the top and runs clockwise
+                        let angle = -PI / 2.0 + 2.0 * PI * left * i as f32 / segments as f32;
+                        node_pos + ring_radius * Vec2::from_angle(angle)
+                    })
+                    .collect();
+                let color = if left < 0.25 {
+                    graphics::Color::RED
+                } else {
+                    graphics::Color::WHITE
+                };
+                mb.line(&ring, 3.0 * scale, color).unwrap();
+            }
         };
 
         let add_target_line = |mb: &mut graphics::MeshBuilder, node: &Node| {
             let node_pos = wtsc(node.pos);
-            let player_pos = wtsc(self.player.pos);
-            mb.line(&[node_pos, player_pos], 5.0, node.color).unwrap();
+            let player_pos = wtsc(self.sim.player.pos);
+            let color = display_color(node.color, self.settings.colorblind);
+            mb.line(&[node_pos, player_pos], 5.0 * scale, color)
+                .unwrap();
         };
 
-        match self.attached_node {
-            Attach::SUCCESS(node, _) => {
-                add_line(mb, &node);
+        match self.sim.attached_node {
+            Attach::SUCCESS(node, is_clockwise) => {
+                add_line(mb, &node, is_clockwise);
+                if self.settings.launch_preview {
+                    for i in 1..=LAUNCH_PREVIEW_DOTS {
+                        let time = LAUNCH_PREVIEW_TIME * i as f32 / LAUNCH_PREVIEW_DOTS as f32;
+                        mb.circle(
+                            graphics::DrawMode::fill(),
+                            wtsc(self.sim.launch_point(time)),
+                            2.0 * scale,
+                            1.0 / detail,
+                            graphics::Color::new(1.0, 1.0, 1.0, 0.6),
+                        )
+                        .unwrap();
+                    }
+                }
             }
             Attach::TARGET(node, _) => {
                 add_target_line(mb, &node);
             }
             Attach::None => {}
         };
-        if self.prev_points.len() > 1 {
-            let prev_points: Vec<Vec2> = self
-                .prev_points
-                .iter()
-                .map(|p| {
-                    world_to_screen_coords(self.screen_width, self.screen_height, *p, coord_origin)
-                })
-                .collect();
-            // Draw THE line!
-            mb.line(&prev_points, 5.0, graphics::Color::WHITE).unwrap();
+        if let Attach::None = self.sim.attached_node {
+            // Show what a press would grab before it happens
+            match self.sim.select_attach(self.steer, self.target_skip) {
+                Attach::SUCCESS(node, _) | Attach::TARGET(node, _) => {
+                    self.add_reticle(mb, &node, coord_origin);
+                }
+                Attach::None => {}
+            }
+        }
+        if self.settings.alignment_meter {
+            if let Some(node) = self.sim.prospective_node(self.steer, self.target_skip) {
+                self.add_alignment_meter(mb, self.sim.alignment(&node), wtsc(self.sim.player.pos));
+            }
+        }
+        if self.near_miss_flash > 0.0 {
+            let alpha = self.near_miss_flash / NEAR_MISS_FLASH_TIME;
+            mb.circle(
+                graphics::DrawMode::stroke(3.0 * scale),
+                wtsc(self.sim.player.pos),
+                self.sim.player.bbox * RATIO * NEAR_MISS_RATIO * 3.0,
+                1.0 / detail,
+                graphics::Color::new(1.0, 1.0, 0.0, alpha),
+            )
+            .unwrap();
+        }
+        self.add_player_trail(mb, &self.sim, coord_origin);
+        for crumb in &self.breadcrumbs {
+            mb.circle(
+                graphics::DrawMode::fill(),
+                wtsc(*crumb),
+                4.0 * scale,
+                1.0 / detail,
+                graphics::Color::new(1.0, 1.0, 1.0, 0.6),
+            )
+            .unwrap();
+        }
+        if let Some(rival) = &self.rival {
+            self.add_rival(mb, rival, coord_origin);
+        }
+        if self.settings.progress_bar {
+            self.add_progress_bar(mb);
+        }
+        if self.debug {
+            let dt = frame_time(ctx.time.delta().as_secs_f32());
+            self.add_velocity_arrows(mb, coord_origin, dt);
+        }
+        // Can be empty now the nodes have a mesh of their own
+        let data = mb.build();
+        if !data.vertices.is_empty() {
+            canvas.draw(
+                &graphics::Mesh::from_data(ctx, data),
+                graphics::DrawParam::new(),
+            );
+        }
+        // Overlays and the HUD stay the right way up
+        canvas.set_screen_coordinates(screen_rect);
+        if effects && self.sim.wind.abs() > 0.05 {
+            let wind_mb = &mut graphics::MeshBuilder::new();
+            self.add_wind_particles(wind_mb);
+            let wind = graphics::Mesh::from_data(ctx, wind_mb.build());
+            canvas.draw(&wind, graphics::DrawParam::new());
+        }
+        if let Some(grace) = self.sim.outside_grace() {
+            self.draw_outside_warning(ctx, &mut canvas, grace);
+        }
+        if self.debug {
+            let graph_mb = &mut graphics::MeshBuilder::new();
+            self.add_frame_graph(graph_mb);
+            let graph = graphics::Mesh::from_data(ctx, graph_mb.build());
+            canvas.draw(&graph, graphics::DrawParam::new());
+            canvas.draw(
+                &graphics::Text::new(format!("FPS: {:.0}", ctx.time.fps())),
+                graphics::DrawParam::from(Vec2::new(
+                    self.screen_width - FRAME_GRAPH_LEN as f32 - 10.0,
+                    self.screen_height - 80.0,
+                ))
+                .color(graphics::Color::WHITE),
+            );
         }
-        let mesh = graphics::Mesh::from_data(ctx, mb.build());
-        canvas.draw(&mesh, graphics::DrawParam::new());
 
         let score_dest = Vec2::new(10.0, 10.0);
-        let score_str = format!("Level: {}", self.player.pos.y.round());
+        let score_str = format!(
+            "Height: {}  Best: {}",
+            self.max_height.round(),
+            self.save_data.best_height.max(self.max_height).round()
+        );
 
         canvas.draw(
             &graphics::Text::new(score_str),
             graphics::DrawParam::from(score_dest).color(ggez::graphics::Color::WHITE),
         );
+        let points_str = format!(
+            "Score: {}  Best: {}",
+            self.score,
+            self.save_data.high_score.max(self.score)
+        );
+        canvas.draw(
+            &graphics::Text::new(points_str),
+            graphics::DrawParam::from(score_dest + Vec2::new(0.0, 20.0))
+                .color(ggez::graphics::Color::WHITE),
+        );
+        let distance_str = format!(
+            "Distance: {:.0}  Total: {:.0}",
+            self.sim.distance,
+            self.save_data.total_distance + self.sim.distance
+        );
+        canvas.draw(
+            &graphics::Text::new(distance_str),
+            graphics::DrawParam::from(score_dest + Vec2::new(0.0, 40.0))
+                .color(ggez::graphics::Color::WHITE),
+        );
+        if self.sim.mode == GameMode::TimeAttack {
+            let color = if self.time_left < 5.0 {
+                graphics::Color::RED
+            } else {
+                graphics::Color::WHITE
+            };
+            let mut clock = graphics::Text::new(format!("{:.1}", self.time_left));
+            clock.set_scale(48.0);
+            let width = clock.measure(ctx)?.x;
+            canvas.draw(
+                &clock,
+                graphics::DrawParam::from(Vec2::new((self.screen_width - width) / 2.0, 40.0))
+                    .color(color),
+            );
+        }
+        self.draw_combo(&mut canvas, ctx, score_dest + Vec2::new(0.0, 100.0))?;
+        if self.rival.is_some() {
+            canvas.draw(
+                &graphics::Text::new(format!(
+                    "Player 1: {}  Player 2: {}",
+                    self.versus_wins[0], self.versus_wins[1]
+                )),
+                graphics::DrawParam::from(score_dest + Vec2::new(0.0, 80.0)).color(RIVAL_COLOR),
+            );
+        }
+        if self.settings.adaptive_difficulty {
+            let (label, color) = if self.ease > 0.05 {
+                ("Adaptive: easier", graphics::Color::GREEN)
+            } else if self.ease < -0.05 {
+                ("Adaptive: harder", graphics::Color::RED)
+            } else {
+                ("Adaptive: normal", graphics::Color::WHITE)
+            };
+            canvas.draw(
+                &graphics::Text::new(label),
+                graphics::DrawParam::from(score_dest + Vec2::new(0.0, 60.0)).color(color),
+            );
+        }
+        for (i, (text, time)) in self.toasts.iter().enumerate() {
+            let alpha = (time / 0.5).min(1.0);
+            canvas.draw(
+                &graphics::Text::new(text.as_str()),
+                graphics::DrawParam::from(Vec2::new(
+                    10.0,
+                    self.screen_height - 30.0 - 20.0 * i as f32,
+                ))
+                .color(graphics::Color::new(1.0, 0.85, 0.0, alpha)),
+            );
+        }
+        if let Some(message) = self.last_death.message().filter(|_| self.intro_timer > 0.0) {
+            let alpha = (self.intro_timer / INTRO_TIME * 2.0).min(1.0);
+            canvas.draw(
+                &graphics::Text::new(message),
+                graphics::DrawParam::from(Vec2::new(10.0, self.screen_height / 2.0))
+                    .color(graphics::Color::new(1.0, 0.3, 0.3, alpha)),
+            );
+        }
+        if self.is_paused() {
+            canvas.draw(
+                &graphics::Text::new("Paused - press P to resume"),
+                graphics::DrawParam::from(Vec2::new(10.0, self.screen_height / 2.0))
+                    .color(graphics::Color::WHITE),
+            );
+            if let Some(seed) = self.seed {
+                canvas.draw(
+                    &graphics::Text::new(format!("Seed {} - Ctrl+C to copy", seed)),
+                    graphics::DrawParam::from(Vec2::new(10.0, self.screen_height / 2.0 + 24.0))
+                        .color(graphics::Color::new(1.0, 1.0, 1.0, 0.7)),
+                );
+            }
+        }
+        self.draw_fade(ctx, &mut canvas)?;
 
         canvas.finish(ctx)?;
         Ok(())
     }
     // Handle key events.  These just map keyboard events
     // and alter our input state appropriately.
-    fn key_down_event(
-        &mut self,
-        ctx: &mut Context,
-        input: KeyInput,
-        _repeated: bool,
-    ) -> GameResult {
+    fn key_down_event(&mut self, ctx: &mut Context, input: KeyInput, repeated: bool) -> GameResult {
+        if input.keycode == Some(KeyCode::F11) && !repeated {
+            return self.toggle_fullscreen(ctx);
+        }
+        if self.fade_timer > 0.0 {
+            return Ok(());
+        }
+        if let Some(keycode) = input.keycode {
+            match self.screen {
+                Screen::Menu => return self.handle_menu_key(ctx, keycode),
+                Screen::Settings => {
+                    self.handle_settings_key(keycode);
+                    return Ok(());
+                }
+                Screen::NameEntry => {
+                    self.handle_name_entry_key(keycode);
+                    return Ok(());
+                }
+                Screen::Playing => {}
+            }
+        }
+        // Before the freeze check, since the seed is only shown while paused
+        if input.keycode == Some(KeyCode::C) && input.mods.contains(KeyMods::CTRL) {
+            self.copy_seed();
+            return Ok(());
+        }
+        let frozen = self.is_paused() || self.hitstop_timer > 0.0;
+        if frozen && !matches!(input.keycode, Some(KeyCode::P | KeyCode::Escape)) {
+            return Ok(());
+        }
         match input.keycode {
-            Some(KeyCode::Space) => {
-                self.handle_button_press();
+            // Only the initial press counts, not OS key repeat while it's held
+            Some(KeyCode::Space) if !repeated => {
+                // Skips the intro as well as pressing as normal
+                self.intro_timer = 0.0;
+                self.steer = steer_input(ctx);
+                self.handle_button_press(ctx)?;
+                if let Attach::None = self.sim.attached_node {
+                    self.buffered_press_timer = INPUT_BUFFER_TIME;
+                }
+            }
+            Some(KeyCode::E) => {
+                // Dump the current course so it can be shared or replayed
+                let level = level::Level {
+                    nodes: self.sim.nodes.clone(),
+                    finish_y: Some(self.sim.finish_y),
+                    start: Some(self.sim.start),
+                };
+                if let Err(e) = level::save_level(path::Path::new(EXPORT_PATH), &level) {
+                    error!("{}", e);
+                }
+            }
+            Some(KeyCode::G) => {
+                self.settings.show_ghost = !self.settings.show_ghost;
+                self.save_settings();
+            }
+            Some(KeyCode::X) if self.settings.panic_detach && !repeated => {
+                self.sim.panic_detach(self.config.panic_cooldown);
+            }
+            Some(KeyCode::Tab) => {
+                if let Attach::None = self.sim.attached_node {
+                    // Pass over the highlighted node for the next best one
+                    self.target_skip += 1;
+                }
+            }
+            Some(KeyCode::F3) => self.debug = !self.debug,
+            Some(KeyCode::F5) => self.save_snapshot(),
+            Some(KeyCode::F9) => self.load_snapshot(),
+            Some(KeyCode::Return) if !repeated => {
+                let attached = match &mut self.rival {
+                    Some(rival) => rival.handle_button_press(0.0, 0).is_some(),
+                    None => false,
+                };
+                if attached {
+                    self.play_attach_sound(ctx)?;
+                }
+            }
+            Some(KeyCode::P) => self.paused = !self.paused,
+            Some(KeyCode::Escape) => {
+                self.paused = false;
+                self.change_screen(Screen::Menu);
             }
-            Some(KeyCode::Escape) => ctx.request_quit(),
             _ => (), // Do nothing
         }
         Ok(())
     }
 
+    /// Debug only: the wheel teleports the player up and down the course
+    /// for a quick look at any section of it
+    fn mouse_wheel_event(&mut self, _ctx: &mut Context, _x: f32, y: f32) -> GameResult {
+        if !self.debug || self.screen != Screen::Playing || self.rival.is_some() {
+            return Ok(());
+        }
+        self.sim.attached_node = Attach::None;
+        self.sim.player.pos.y += y * SCRUB_STEP;
+        // Teleporting shouldn't count as a move through everything in between
+        self.sim.prev_pos = self.sim.player.pos;
+        self.sim.prev_points.clear();
+        self.scrub_timer = SCRUB_GRACE_TIME;
+        self.scrubbed = true;
+        Ok(())
+    }
+
+    /// Last chance to write everything out, since nothing after
+    /// `event::run` is guaranteed to happen. A run still in progress counts
+    /// as finished, so its distance and any record aren't lost.
+    fn quit_event(&mut self, _ctx: &mut Context) -> GameResult<bool> {
+        info!("Quitting, saving progress and settings");
+        self.finish_run();
+        self.save_progress();
+        self.save_settings();
+        // Not cancelling the quit
+        Ok(false)
+    }
+
+    /// Pauses while the window is in the background. Kept apart from the
+    /// manual pause so regaining focus doesn't unpause a paused game.
+    fn focus_event(&mut self, _ctx: &mut Context, gained: bool) -> GameResult {
+        self.focus_lost = !gained;
+        Ok(())
+    }
+
+    fn text_input_event(&mut self, _ctx: &mut Context, character: char) -> GameResult {
+        if self.screen == Screen::NameEntry
+            && self.fade_timer <= 0.0
+            && character.is_ascii_alphanumeric()
+            && self.initials.len() < INITIALS_LEN
+        {
+            self.initials.push(character.to_ascii_uppercase());
+        }
+        Ok(())
+    }
+
     fn key_up_event(&mut self, _ctx: &mut Context, input: KeyInput) -> GameResult {
         match input.keycode {
             Some(KeyCode::Space) => {
-                self.attached_node = Attach::None;
+                self.sim.detach();
+                self.buffered_press_timer = 0.0;
+                self.target_skip = 0;
+            }
+            Some(KeyCode::Return) => {
+                if let Some(rival) = &mut self.rival {
+                    rival.detach();
+                }
             }
             _ => (), // Do nothing
         }
@@ -500,10 +3714,50 @@ impl ggez::event::EventHandler<GameError> for State {
     }
 }
 
+#[derive(Debug, Default)]
+struct Args {
+    level: Option<path::PathBuf>,
+    /// Generates this course rather than one seeded from the clock
+    seed: Option<u64>,
+    /// Where to load assets from instead of the cargo project
+    resources: Option<path::PathBuf>,
+}
+
+fn parse_args() -> Args {
+    let mut args = Args::default();
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "--level" => match iter.next() {
+                Some(level) => args.level = Some(path::PathBuf::from(level)),
+                None => warn!("--level needs a path, ignoring it"),
+            },
+            "--resources" => match iter.next() {
+                Some(dir) => args.resources = Some(path::PathBuf::from(dir)),
+                None => warn!("--resources needs a path, ignoring it"),
+            },
+            "--seed" => match iter.next().map(|s| s.parse()) {
+                Some(Ok(seed)) => args.seed = Some(seed),
+                _ => warn!("--seed needs a whole number, ignoring it"),
+            },
+            _ => warn!("Ignoring unknown argument {}", arg),
+        }
+    }
+    args
+}
+
 fn main() {
-    // We add the CARGO_MANIFEST_DIR/resources to the resource paths
+    logger::init();
+    let args = parse_args();
+    // An explicit resource dir wins, for when the binary is packaged on its own.
+    // Otherwise we add the CARGO_MANIFEST_DIR/resources to the resource paths
     // so that ggez will look in our cargo project directory for files.
-    let resource_dir = if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
+    let resource_dir = if let Some(dir) = args
+        .resources
+        .or_else(|| env::var_os(RESOURCES_ENV).map(path::PathBuf::from))
+    {
+        dir
+    } else if let Ok(manifest_dir) = env::var("CARGO_MANIFEST_DIR") {
         let mut path = path::PathBuf::from(manifest_dir);
         path.push("resources");
         path
@@ -512,10 +3766,602 @@ fn main() {
     };
     // let resource_dir = path::PathBuf::from("./resources");
 
+    // Vsync is fixed when the window is created, so the setting has to be
+    // read before there's a context to find the config dir with
+    let settings = filesystem::Filesystem::new("hello_ggez", "aydin", "resources", "resources.zip")
+        .and_then(|fs| {
+            Settings::load(&fs.user_config_dir().join(SETTINGS_FILE)).map_err(GameError::from)
+        })
+        .unwrap_or_default();
     let cb = ContextBuilder::new("hello_ggez", "aydin")
-        .window_mode(conf::WindowMode::default().dimensions(SCREEN_WIDTH, SCREEN_HEIGHT))
+        .window_setup(conf::WindowSetup::default().vsync(settings.vsync))
+        .window_mode(
+            conf::WindowMode::default()
+                .dimensions(SCREEN_WIDTH, SCREEN_HEIGHT)
+                .fullscreen_type(fullscreen_type(settings.fullscreen)),
+        )
         .add_resource_path(resource_dir);
-    let (mut ctx, event_loop) = cb.build().unwrap();
-    let state = State::new(&mut ctx).unwrap();
+    let (mut ctx, event_loop) = cb.build().unwrap_or_else(|e| {
+        error!("Couldn't open the game window: {}", e);
+        std::process::exit(1);
+    });
+    let state = match args.level {
+        Some(level_path) => match State::from_level_file(&mut ctx, &level_path) {
+            Ok(state) => state,
+            Err(e) => {
+                error!("{}", e);
+                std::process::exit(1);
+            }
+        },
+        None => State::new(&mut ctx, args.seed).unwrap_or_else(|e| {
+            error!("{}", e);
+            std::process::exit(1);
+        }),
+    };
     event::run(ctx, event_loop, state);
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn node_at(pos: Vec2) -> Node {
+        Node {
+            pos,
+            radius: 0.1,
+            color: graphics::Color::WHITE,
+            kind: NodeKind::Normal,
+            used: false,
+        }
+    }
+
+    fn sim_with_node_at(pos: Vec2) -> Sim {
+        Sim::new(vec![node_at(pos)]).unwrap()
+    }
+
+    fn test_sim() -> Sim {
+        sim_with_node_at(Vec2::new(0.0, 1.0))
+    }
+
+    fn assert_close(a: Vec2, b: Vec2) {
+        assert!(a.distance(b) < 1e-5, "{:?} != {:?}", a, b);
+    }
+
+    #[test]
+    fn facing_zero_points_up() {
+        assert_close(direction(0.0), Vec2::Y);
+    }
+
+    #[test]
+    fn facing_quarter_turns_rotate_clockwise() {
+        assert_close(direction(PI / 2.0), Vec2::X);
+        assert_close(direction(PI), Vec2::NEG_Y);
+        assert_close(direction(-PI / 2.0), Vec2::NEG_X);
+        for facing in [0.0, 0.3, 1.0, 2.5, -1.2] {
+            let rotated = Vec2::from_angle(-PI / 2.0).rotate(direction(facing));
+            assert_close(direction(facing + PI / 2.0), rotated);
+        }
+    }
+
+    #[test]
+    fn direction_is_unit_length() {
+        for facing in [0.0, 0.7, PI, 4.0] {
+            assert!((direction(facing).length() - 1.0).abs() < 1e-5);
+        }
+    }
+
+    #[test]
+    fn non_finite_position_is_detected() {
+        let mut sim = test_sim();
+        sim.player.pos = Vec2::new(f32::NAN, 2.0);
+        sim.step(1.0 / 60.0);
+        assert!(sim.detect_non_finite());
+        // Left for the caller to reset, so the ended run can still be recorded
+        assert!(!sim.player.pos.is_finite());
+        sim.reset();
+        sim.step(1.0 / 60.0);
+        assert!(sim.player.pos.is_finite());
+        assert!(!sim.detect_non_finite());
+    }
+
+    #[test]
+    fn orbiting_from_the_node_centre_recovers() {
+        // A zero orbit radius gives a zero period, which used to poison the position with NaN
+        let mut sim = test_sim();
+        let node = sim.nodes[0];
+        sim.player.pos = node.pos;
+        sim.attached_node = Attach::SUCCESS(node, true);
+        sim.step(1.0 / 60.0);
+        if sim.detect_non_finite() {
+            sim.reset();
+        }
+        assert!(sim.player.pos.is_finite());
+    }
+
+    #[test]
+    fn node_on_the_right_orbits_clockwise() {
+        let player = Player::new(Vec2::ZERO).unwrap();
+        assert!(get_is_clockwise(&player, &node_at(Vec2::new(1.0, 0.0))));
+        assert!(get_is_clockwise(&player, &node_at(Vec2::new(1.0, 2.0))));
+    }
+
+    #[test]
+    fn node_on_the_left_orbits_anticlockwise() {
+        let player = Player::new(Vec2::ZERO).unwrap();
+        assert!(!get_is_clockwise(&player, &node_at(Vec2::new(-1.0, 0.0))));
+        assert!(!get_is_clockwise(&player, &node_at(Vec2::new(-1.0, 2.0))));
+    }
+
+    #[test]
+    fn orbit_direction_follows_facing() {
+        // Heading right, so the right-hand side is below the player
+        let mut player = Player::new(Vec2::ZERO).unwrap();
+        player.facing = PI / 2.0;
+        assert!(get_is_clockwise(&player, &node_at(Vec2::new(0.0, -1.0))));
+        assert!(!get_is_clockwise(&player, &node_at(Vec2::new(0.0, 1.0))));
+    }
+
+    #[test]
+    fn node_in_line_orbits_clockwise() {
+        let player = Player::new(Vec2::ZERO).unwrap();
+        assert!(get_is_clockwise(&player, &node_at(Vec2::new(0.0, 1.0))));
+        assert!(get_is_clockwise(&player, &node_at(Vec2::new(0.0, -5.0))));
+        // Within the tolerance either lean is clockwise, past it the side decides
+        assert!(get_is_clockwise(&player, &node_at(Vec2::new(1e-5, 1.0))));
+        assert!(get_is_clockwise(&player, &node_at(Vec2::new(-1e-5, 1.0))));
+        assert!(!get_is_clockwise(&player, &node_at(Vec2::new(-1e-2, 1.0))));
+    }
+
+    #[test]
+    fn checkpoint_respawn_is_clear_of_nodes_and_walls() {
+        let mut sim = sim_with_node_at(Vec2::new(0.0, CHECKPOINT_SPACING + 0.05));
+        sim.player.pos = Vec2::new(0.0, CHECKPOINT_SPACING + 0.1);
+        assert_eq!(sim.next_checkpoint(None), None, "on top of a node");
+        sim.player.pos = Vec2::new(sim.half_width, CHECKPOINT_SPACING + 0.5);
+        assert_eq!(sim.next_checkpoint(None), None, "over the wall");
+        let spot = Vec2::new(0.5, CHECKPOINT_SPACING + 0.5);
+        sim.player.pos = spot;
+        assert_eq!(sim.next_checkpoint(None), Some(spot));
+        assert_eq!(sim.next_checkpoint(Some(spot)), None, "already taken");
+
+        sim.respawn(spot);
+        assert_eq!(sim.player.pos, spot);
+        assert!(sim.prev_points.is_empty());
+        sim.step(1.0 / 60.0);
+        assert_eq!(sim.handle_collision(), CollisionCause::None);
+    }
+
+    #[test]
+    fn press_beside_a_node_attaches_straight_away() {
+        let mut sim = sim_with_node_at(Vec2::new(0.5, 0.02));
+        let node = sim.handle_button_press(0.0, 0).expect("should attach");
+        assert_eq!(node.pos, Vec2::new(0.5, 0.02));
+        assert!(matches!(sim.attached_node, Attach::SUCCESS(n, true) if n.pos == node.pos));
+    }
+
+    #[test]
+    fn press_towards_a_node_targets_it_until_aligned() {
+        let target = Vec2::new(0.5, 1.0);
+        let mut sim = sim_with_node_at(target);
+        assert!(sim.handle_button_press(0.0, 0).is_none());
+        assert!(matches!(sim.attached_node, Attach::TARGET(n, true) if n.pos == target));
+
+        let attached = (0..100).find_map(|_| sim.step(0.01));
+        assert_eq!(attached.map(|n| n.pos), Some(target));
+        assert!(matches!(sim.attached_node, Attach::SUCCESS(n, true) if n.pos == target));
+    }
+
+    #[test]
+    fn attach_assist_lines_up_a_near_miss_sooner() {
+        // Ahead and to the right, 20 degrees short of side on
+        let target = Vec2::from_angle(-70f32.to_radians()).rotate(Vec2::Y);
+        let steps_to_attach = |assist: f32| {
+            let mut sim = sim_with_node_at(target);
+            sim.attach_assist = assist;
+            assert!(sim.handle_button_press(0.0, 0).is_none());
+            (0..100).position(|_| sim.step(0.01).is_some())
+        };
+        let unassisted = steps_to_attach(0.0).expect("should attach eventually");
+        let assisted = steps_to_attach(ATTACH_ASSIST_TURN).expect("should attach");
+        assert!(assisted < unassisted, "{} vs {}", assisted, unassisted);
+    }
+
+    #[test]
+    fn press_falls_back_to_a_node_behind() {
+        // Behind the player, so only the fallback search will take it
+        let mut sim = sim_with_node_at(Vec2::new(-0.5, -0.5));
+        let node = sim.handle_button_press(0.0, 0).expect("should attach");
+        assert_eq!(node.pos, Vec2::new(-0.5, -0.5));
+        assert!(matches!(sim.attached_node, Attach::SUCCESS(_, false)));
+    }
+
+    #[test]
+    fn press_with_only_a_node_dead_ahead_does_nothing() {
+        // Orbiting it would mean flying straight into it
+        let mut sim = sim_with_node_at(Vec2::new(0.0, 1.0));
+        assert!(sim.handle_button_press(0.0, 0).is_none());
+        assert!(matches!(sim.attached_node, Attach::None));
+    }
+
+    #[test]
+    fn press_while_attached_changes_nothing() {
+        let mut sim = sim_with_node_at(Vec2::new(0.5, 0.02));
+        sim.handle_button_press(0.0, 0);
+        let before = sim.attaches;
+        assert!(sim.handle_button_press(0.0, 0).is_none());
+        assert_eq!(sim.attaches, before);
+        assert!(matches!(sim.attached_node, Attach::SUCCESS(_, _)));
+    }
+
+    /// How far round `node` the player turns in one orbit step from `radius` away
+    fn orbit_angle(radius: f32, constant_angular_speed: bool) -> f32 {
+        let node = node_at(Vec2::ZERO);
+        let mut player = Player::new(Vec2::ZERO).unwrap();
+        player.pos = Vec2::new(radius, 0.0);
+        player.orbit(&node, 0.01, false, constant_angular_speed, 0.0);
+        Vec2::X.angle_between(player.pos)
+    }
+
+    #[test]
+    fn default_orbits_keep_linear_speed() {
+        let (wide, tight) = (orbit_angle(1.0, false), orbit_angle(0.25, false));
+        assert!((tight / wide - 4.0).abs() < 1e-3, "{} vs {}", tight, wide);
+    }
+
+    #[test]
+    fn constant_angular_speed_ignores_radius() {
+        let (wide, tight) = (orbit_angle(1.0, true), orbit_angle(0.25, true));
+        assert!((tight - wide).abs() < 1e-5, "{} vs {}", tight, wide);
+        let expected = Player::new(Vec2::ZERO).unwrap().speed / ORBIT_REFERENCE_RADIUS * 0.01;
+        assert!((wide - expected).abs() < 1e-5);
+    }
+
+    #[test]
+    fn orbit_decay_tightens_at_its_rate() {
+        let node = node_at(Vec2::ZERO);
+        let mut player = Player::new(Vec2::ZERO).unwrap();
+        player.pos = Vec2::new(1.0, 0.0);
+        for _ in 0..100 {
+            player.orbit(&node, 0.01, false, false, 0.1);
+        }
+        let radius = player.pos.length();
+        assert!((radius - 0.9).abs() < 1e-4, "radius {}", radius);
+
+        // Without decay the orbit holds its size
+        player.pos = Vec2::new(1.0, 0.0);
+        for _ in 0..100 {
+            player.orbit(&node, 0.01, false, false, 0.0);
+        }
+        assert!((player.pos.length() - 1.0).abs() < 1e-4);
+    }
+
+    #[test]
+    fn decayed_orbit_ends_on_the_node() {
+        let mut sim = test_sim();
+        sim.orbit_decay = 1.0;
+        let node = sim.nodes[0];
+        sim.player.pos = node.pos + Vec2::new(0.5, 0.0);
+        sim.attached_node = Attach::SUCCESS(node, true);
+        assert_eq!(sim.handle_collision(), CollisionCause::None);
+        for _ in 0..100 {
+            sim.step(0.01);
+        }
+        assert!((sim.player.pos.distance(node.pos) - node.radius).abs() < 1e-4);
+        assert_eq!(sim.handle_collision(), CollisionCause::Node);
+    }
+
+    #[test]
+    fn attach_reach_bounds_eligible_nodes() {
+        let player = Player::new(Vec2::ZERO).unwrap();
+        let beside_path =
+            |distance: f32| node_at(Vec2::new(0.3, (distance * distance - 0.09).sqrt()));
+        for reach in [GameConfig::default().attach_reach, 3.0] {
+            assert!(filter_deadly_nodes(
+                &player,
+                &beside_path(reach - 0.01),
+                10.0,
+                reach
+            ));
+            assert!(!filter_deadly_nodes(
+                &player,
+                &beside_path(reach + 0.01),
+                10.0,
+                reach
+            ));
+        }
+        assert_eq!(GameConfig::default().attach_reach, 2.0);
+    }
+
+    #[test]
+    fn zero_drawable_size_falls_back_to_the_window_size() {
+        assert_eq!(usable_size((0.0, 0.0)), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert_eq!(usable_size((800.0, 0.0)), (SCREEN_WIDTH, SCREEN_HEIGHT));
+        assert_eq!(
+            usable_size((f32::NAN, 600.0)),
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        );
+        assert_eq!(usable_size((800.0, 600.0)), (800.0, 600.0));
+    }
+
+    #[test]
+    fn attract_mode_survives_the_opening() {
+        let nodes = make_nodes(
+            0,
+            1000,
+            &mut Rand32::new(3),
+            &COLORS,
+            &GameConfig::default(),
+            ColorStrategy::Sequential,
+            0,
+        );
+        let mut attract = Attract::new(Sim::new(nodes).unwrap());
+        for _ in 0..300 {
+            attract.update(1.0 / 60.0);
+        }
+        // A crash would have started it over
+        assert!(
+            attract.sim.run_time > 4.9,
+            "crashed at {}",
+            attract.sim.run_time
+        );
+        assert!(attract.sim.attaches > 0);
+    }
+
+    #[test]
+    fn max_orbit_radius_rejects_wide_orbits() {
+        // Flying straight up past a node half a unit to the side
+        let mut sim = sim_with_node_at(Vec2::new(0.5, 1.0));
+        assert!(!matches!(sim.select_attach(0.0, 0), Attach::None));
+        sim.max_orbit_radius = Some(0.6);
+        assert!(!matches!(sim.select_attach(0.0, 0), Attach::None));
+        sim.max_orbit_radius = Some(0.4);
+        assert!(matches!(sim.select_attach(0.0, 0), Attach::None));
+        assert!(sim.handle_button_press(0.0, 0).is_none());
+    }
+
+    #[test]
+    fn alignment_fills_as_the_node_comes_side_on() {
+        let mut sim = test_sim();
+        let node = sim.nodes[0];
+        // Heading straight at it
+        assert_eq!(sim.alignment(&node), 0.0);
+        // Just inside the attach threshold
+        sim.player.facing = (ATTACH_ALIGNMENT * 0.9).acos();
+        assert_eq!(sim.alignment(&node), 1.0);
+        sim.player.facing = PI / 4.0;
+        let halfway = sim.alignment(&node);
+        assert!(0.0 < halfway && halfway < 1.0, "{}", halfway);
+    }
+
+    #[test]
+    fn rainbow_color_loops_through_the_palette() {
+        let rgb = |c: graphics::Color| (c.r, c.g, c.b);
+        assert_eq!(rgb(rainbow_color(0.0, false)), rgb(COLORS[0]));
+        assert_eq!(rgb(rainbow_color(2.0, false)), rgb(COLORS[2]));
+        assert_eq!(
+            rgb(rainbow_color(COLORS.len() as f32, false)),
+            rgb(COLORS[0])
+        );
+        assert_eq!(
+            rgb(rainbow_color(-1.0, false)),
+            rgb(COLORS[COLORS.len() - 1])
+        );
+        // Halfway between white and magenta
+        assert_eq!(rgb(rainbow_color(0.5, false)), (1.0, 0.5, 1.0));
+    }
+
+    #[test]
+    fn menu_selection_wraps_around() {
+        let mut menu = Menu::new(vec!['a', 'b', 'c']);
+        assert_eq!(menu.select(), 'a');
+        menu.move_up();
+        assert_eq!(menu.select(), 'c');
+        menu.move_down();
+        assert_eq!(menu.select(), 'a');
+        menu.highlight('c');
+        menu.move_down();
+        assert_eq!(menu.select(), 'a');
+    }
+
+    #[test]
+    fn selected_setting_is_always_on_screen() {
+        let fits = ((SCREEN_HEIGHT - MENU_TOP) / MENU_ROW_HEIGHT) as usize;
+        for selected in 0..SETTING_ITEMS.len() {
+            let shown = menu::visible_rows(SETTING_ITEMS.len(), selected, fits);
+            assert!(shown.contains(&selected));
+            assert!(shown.len() <= fits);
+            let row = (selected - shown.start) as f32;
+            assert!(MENU_TOP + MENU_ROW_HEIGHT * (row + 1.0) <= SCREEN_HEIGHT);
+        }
+        // Short menus show everything
+        assert_eq!(menu::visible_rows(3, 2, fits), 0..3);
+    }
+
+    #[test]
+    fn cycling_wraps_and_restarts_unknown_values() {
+        let list = [1, 2, 3];
+        assert_eq!(menu::cycle(&list, 3, true), 1);
+        assert_eq!(menu::cycle(&list, 1, false), 3);
+        assert_eq!(menu::cycle(&list, 7, true), 2);
+    }
+
+    #[test]
+    fn generated_nodes_climb_the_corridor() {
+        for difficulty in config::DIFFICULTIES {
+            let config = difficulty.config();
+            let nodes = make_nodes(
+                0,
+                1000,
+                &mut Rand32::new(7),
+                &COLORS,
+                &config,
+                ColorStrategy::Sequential,
+                0,
+            );
+            let half_width = config.corridor_width / 2.0;
+            for node in &nodes {
+                assert!(
+                    node.pos.x.abs() <= half_width,
+                    "{} outside the corridor",
+                    node.pos
+                );
+            }
+            // Jitter is under a unit either way, so nodes never swap order
+            // and never bunch up by more than that
+            for pair in nodes.windows(2) {
+                let gap = pair[1].pos.y - pair[0].pos.y;
+                assert!(
+                    gap > config.node_spacing - 1.0,
+                    "{:?}: gap of {} between {} and {}",
+                    difficulty,
+                    gap,
+                    pair[0].pos,
+                    pair[1].pos
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn lanes_line_nodes_up_in_columns() {
+        let config = GameConfig::default();
+        let lane_width = config.corridor_width / 3.0;
+        let centres = [-lane_width, 0.0, lane_width];
+        let nodes = make_nodes(
+            0,
+            1000,
+            &mut Rand32::new(7),
+            &COLORS,
+            &config,
+            ColorStrategy::Sequential,
+            3,
+        );
+        for node in &nodes {
+            assert!(
+                centres.iter().any(|c| (node.pos.x - c).abs() < 1e-4),
+                "{} isn't in a lane",
+                node.pos
+            );
+        }
+        // Every lane gets used
+        for c in centres {
+            assert!(nodes.iter().any(|n| (n.pos.x - c).abs() < 1e-4));
+        }
+    }
+
+    #[test]
+    fn node_radii_stay_within_configured_bounds() {
+        let configs = [
+            GameConfig::default(),
+            GameConfig {
+                min_node_radius: 0.1,
+                max_node_radius: 0.12,
+                ..GameConfig::default()
+            },
+            GameConfig {
+                min_node_radius: 0.3,
+                max_node_radius: 0.3,
+                ..GameConfig::default()
+            },
+        ];
+        for config in configs {
+            let (min, max) = config.node_radius_range();
+            let nodes = make_nodes(
+                0,
+                1000,
+                &mut Rand32::new(7),
+                &COLORS,
+                &config,
+                ColorStrategy::Sequential,
+                0,
+            );
+            for node in nodes {
+                assert!(
+                    (min..=max).contains(&node.radius),
+                    "{} outside {}..={}",
+                    node.radius,
+                    min,
+                    max
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn trail_of_repeated_points_builds() {
+        let mb = &mut graphics::MeshBuilder::new();
+        add_trail(
+            mb,
+            &[Vec2::new(10.0, 10.0); 50],
+            5.0,
+            graphics::Color::WHITE,
+        );
+        let stalled = [
+            Vec2::ZERO,
+            Vec2::ZERO,
+            Vec2::new(0.0, 5.0),
+            Vec2::new(0.0, 5.0),
+        ];
+        add_trail(mb, &stalled, 5.0, graphics::Color::WHITE);
+        assert!(!mb.build().vertices.is_empty());
+    }
+
+    #[test]
+    fn long_frames_are_clamped() {
+        assert_eq!(frame_time(1.0 / 60.0), 1.0 / 60.0);
+        assert_eq!(frame_time(0.5), MAX_FRAME_TIME);
+    }
+
+    #[test]
+    fn frame_hitch_does_not_skip_a_node() {
+        // Flying straight up at the node, just short of touching it
+        let mut sim = test_sim();
+        sim.player.pos = Vec2::new(0.0, 0.8);
+        sim.step(frame_time(0.5));
+        assert!(sim.player.pos.y < 1.5);
+        assert_eq!(sim.handle_collision(), CollisionCause::Node);
+    }
+
+    #[test]
+    fn segment_distance_measures_to_the_closest_point() {
+        let (a, b) = (Vec2::ZERO, Vec2::new(0.0, 2.0));
+        assert!((segment_distance(a, b, Vec2::new(0.5, 1.0)) - 0.5).abs() < 1e-5);
+        assert!((segment_distance(a, b, Vec2::new(0.0, 3.0)) - 1.0).abs() < 1e-5);
+        assert!((segment_distance(a, a, Vec2::new(0.0, 1.0)) - 1.0).abs() < 1e-5);
+    }
+
+    /// A sim whose player covers 2.0 units in one step, far more than a node is wide
+    fn fast_sim(start: Vec2) -> Sim {
+        let mut sim = test_sim();
+        sim.player.speed = 40.0;
+        sim.player.pos = start;
+        sim.step(0.05);
+        sim
+    }
+
+    #[test]
+    fn fast_move_through_a_node_collides() {
+        // Both ends of the step are well clear of the node at (0, 1)
+        let sim = fast_sim(Vec2::new(0.1, 0.0));
+        assert!(sim.player.pos.y > 1.5);
+        assert_eq!(sim.handle_collision(), CollisionCause::Node);
+    }
+
+    #[test]
+    fn fast_move_past_a_node_misses() {
+        let sim = fast_sim(Vec2::new(0.2, 0.0));
+        assert_eq!(sim.handle_collision(), CollisionCause::None);
+    }
+
+    #[test]
+    fn fast_move_across_a_wall_collides() {
+        let mut sim = test_sim();
+        sim.player.speed = 40.0;
+        sim.player.facing = PI / 2.0;
+        sim.player.pos = Vec2::new(sim.half_width - 0.5, 0.0);
+        sim.player.time_disconnected = 1.0;
+        sim.step(0.05);
+        assert!(sim.player.pos.x > sim.half_width + 1.0);
+        assert_eq!(sim.handle_collision(), CollisionCause::Side);
+    }
+}
@@ -0,0 +1,61 @@
+use std::ops::Range;
+
+/// The item after `current` in `list`, or the one before if not `forward`,
+/// wrapping round at either end. A hand-edited value that isn't in the list
+/// starts again from the first item.
+pub fn cycle<T: PartialEq + Copy>(list: &[T], current: T, forward: bool) -> T {
+    let i = list.iter().position(|&x| x == current).unwrap_or(0);
+    let step = if forward { 1 } else { list.len() - 1 };
+    list[(i + step) % list.len()]
+}
+
+/// Which of `len` rows to show when only `rows` fit, scrolled to keep
+/// `selected` as near the middle as the ends allow
+pub fn visible_rows(len: usize, selected: usize, rows: usize) -> Range<usize> {
+    let rows = rows.clamp(1, len.max(1));
+    let start = selected.saturating_sub(rows / 2).min(len - rows.min(len));
+    start..(start + rows).min(len)
+}
+
+/// A list of items picked from with the arrow keys. Moving past either end
+/// wraps round to the other.
+#[derive(Debug, Clone)]
+pub struct Menu<T> {
+    items: Vec<T>,
+    selected: usize,
+}
+
+impl<T: Copy + PartialEq> Menu<T> {
+    pub fn new(items: Vec<T>) -> Menu<T> {
+        assert!(!items.is_empty(), "a menu needs something to pick");
+        Menu { items, selected: 0 }
+    }
+
+    pub fn items(&self) -> &[T] {
+        &self.items
+    }
+
+    pub fn selected_index(&self) -> usize {
+        self.selected
+    }
+
+    pub fn move_up(&mut self) {
+        self.selected = (self.selected + self.items.len() - 1) % self.items.len();
+    }
+
+    pub fn move_down(&mut self) {
+        self.selected = (self.selected + 1) % self.items.len();
+    }
+
+    /// The highlighted item
+    pub fn select(&self) -> T {
+        self.items[self.selected]
+    }
+
+    /// Highlights `item`, if it's in the menu
+    pub fn highlight(&mut self, item: T) {
+        if let Some(i) = self.items.iter().position(|&x| x == item) {
+            self.selected = i;
+        }
+    }
+}
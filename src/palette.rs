@@ -0,0 +1,32 @@
+use crate::error::{Error, Result};
+use crate::save::read_json;
+use ggez::graphics::Color;
+use serde::Deserialize;
+use std::path::Path;
+
+/// Node colours as 0-255 RGB triples, so players can theme the game
+/// without recompiling. Colours are handed out to nodes in order, so the
+/// fifth and sixth are the hazard and bonus colours under colour rules.
+#[derive(Debug, Deserialize)]
+struct Palette {
+    colors: Vec<[u8; 3]>,
+}
+
+/// Reads a palette file, or `None` if there isn't one
+pub fn load(path: &Path) -> Result<Option<Vec<Color>>> {
+    let Some(palette) = read_json::<Option<Palette>>(path)? else {
+        return Ok(None);
+    };
+    if palette.colors.is_empty() {
+        return Err(Error::EmptyPalette {
+            path: path.to_path_buf(),
+        });
+    }
+    Ok(Some(
+        palette
+            .colors
+            .iter()
+            .map(|[r, g, b]| Color::from_rgb(*r, *g, *b))
+            .collect(),
+    ))
+}
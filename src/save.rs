@@ -0,0 +1,69 @@
+use crate::error::{Error, Result};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Progress that persists between sessions, stored in the user data dir
+#[derive(Debug, Default, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SaveData {
+    pub high_score: u32,
+    /// Highest point ever reached in a classic run
+    pub best_height: f32,
+    /// Attaches across every non-zen run
+    pub total_attaches: u32,
+    /// Distance flown across every non-zen run
+    pub total_distance: f32,
+    /// Longest chain of attaches in any non-zen run
+    pub best_combo: u32,
+    /// Ids of unlocked achievements, see `ACHIEVEMENTS`
+    pub achievements: Vec<String>,
+}
+
+impl SaveData {
+    pub fn load(path: &Path) -> Result<SaveData> {
+        read_json(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_json(path, self)
+    }
+}
+
+/// Reads `path` as JSON, falling back to the default if there's no file yet
+pub fn read_json<T: DeserializeOwned + Default>(path: &Path) -> Result<T> {
+    let json = match fs::read_to_string(path) {
+        Ok(json) => json,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(T::default()),
+        Err(source) => {
+            return Err(Error::SaveLoad {
+                path: path.to_path_buf(),
+                source,
+            })
+        }
+    };
+    serde_json::from_str(&json).map_err(|source| Error::ConfigParse {
+        path: path.to_path_buf(),
+        source,
+    })
+}
+
+/// Writes `value` as JSON, creating the parent directory if needed
+pub fn write_json<T: Serialize>(path: &Path, value: &T) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|source| Error::SaveLoad {
+            path: dir.to_path_buf(),
+            source,
+        })?;
+    }
+    let json = serde_json::to_string_pretty(value).map_err(|source| Error::ConfigParse {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    fs::write(path, json).map_err(|source| Error::SaveLoad {
+        path: path.to_path_buf(),
+        source,
+    })
+}
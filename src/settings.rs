@@ -0,0 +1,536 @@
+use crate::config::{ColorStrategy, Difficulty};
+use crate::error::Result;
+use crate::menu::cycle;
+use crate::save::{read_json, write_json};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Player preferences, stored as JSON in the user config dir.
+/// Missing fields fall back to their defaults so older files keep loading.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub show_ghost: bool,
+    /// Opt-in: eases off after quick deaths and tightens up during long runs
+    pub adaptive_difficulty: bool,
+    /// Flashes when the player slips past a node without hitting it
+    pub near_miss_feedback: bool,
+    /// How many decorative nodes to scatter behind the playfield
+    pub background_nodes: usize,
+    /// Generate a new course after each death rather than retrying the same one
+    pub reseed_on_reset: bool,
+    /// Overrides the display's DPI factor when scaling line widths
+    pub line_scale: Option<f32>,
+    /// Yellow nodes are worth triple and red ones can't be grabbed.
+    /// Off means colours are purely cosmetic, as in classic play.
+    pub color_rules: bool,
+    /// Nodes go inert after you release them, forcing you onwards
+    pub one_time_nodes: bool,
+    /// Sound effect volume, 0.0 to 1.0
+    pub volume: f32,
+    /// Swaps the node palette for one that's distinguishable with colour blindness
+    pub colorblind: bool,
+    /// How newly generated courses colour their nodes
+    pub color_strategy: ColorStrategy,
+    /// Columns newly generated nodes are lined up in, 0 leaving them anywhere across the corridor
+    pub lanes: u32,
+    pub show_trail: bool,
+    /// Thickness of the trail in pixels, before `line_scale`
+    pub trail_width: f32,
+    /// Faintly shows where the previous run went and where it ended
+    pub show_last_trail: bool,
+    /// Jitters the drawn trail for a glitchy look. Purely cosmetic.
+    pub glitch_trail: bool,
+    /// Colours the trail through the node palette along its length instead of plain white
+    pub rainbow_trail: bool,
+    /// Plain black background instead of one that shifts colour with height
+    pub classic_background: bool,
+    /// Cuts back on drawing for weak hardware, overriding the individual effect toggles
+    pub performance_mode: bool,
+    /// Faint horizontal lines that scroll past, so movement shows even in empty space
+    pub scanlines: bool,
+    /// Draws nodes with the shaded node sprite, where there is one, rather than flat circles
+    pub node_sprites: bool,
+    /// Linear filtering for the player sprite. Off keeps its pixels crisp.
+    pub smooth_player: bool,
+    /// Brightens each wall as the player gets close to it
+    pub wall_warning: bool,
+    /// Thickness of the wall lines in pixels, before `line_scale`
+    pub border_width: f32,
+    /// Red gradient just inside each wall, marking where touching it kills
+    pub danger_zone: bool,
+    /// Dots out the path the player would fly if they let go of the node now
+    pub launch_preview: bool,
+    /// Shows how much of the course is left on a bar at the screen edge
+    pub progress_bar: bool,
+    /// Orbits turn at the same rate whatever their size, instead of keeping linear speed
+    pub constant_angular_speed: bool,
+    /// Eases into the orbit's radius over a moment rather than locking on at whatever distance the attach caught
+    pub smooth_orbit_entry: bool,
+    /// How strongly a targeted attach pulls the player into line, 0.0 (off) to 1.0
+    pub attach_assist: f32,
+    /// Shows a bar under the player that fills as they line up with the node a press would target
+    pub alignment_meter: bool,
+    /// Seconds an orbit can last before letting go by itself, 0.0 for no limit
+    pub max_orbit_time: f32,
+    /// How fast orbits tighten towards the node, in world units per second. 0.0 keeps them steady.
+    pub orbit_decay: f32,
+    /// Combos break when the next attach doesn't come within `combo_window`,
+    /// rather than only on death
+    pub combo_decay: bool,
+    /// Seconds allowed between attaches to keep a combo going
+    pub combo_window: f32,
+    pub orbit_direction: OrbitDirection,
+    /// Mirrors the playfield vertically so the player heads down the screen
+    pub invert_vertical: bool,
+    /// World units between the player and the bottom of the screen. Lower
+    /// puts the player nearer the bottom, showing more of the course ahead.
+    pub camera_lookahead: f32,
+    /// Up and down change speed in free flight, instead of it being fixed
+    pub thrust_control: bool,
+    /// `X` flings the player off a node backwards, as an escape move
+    pub panic_detach: bool,
+    /// Sideways gusts once the player is high enough
+    pub wind: bool,
+    /// Only read at startup, since the window has to be rebuilt to change it
+    pub vsync: bool,
+    /// Toggled with F11 rather than from the settings screen
+    pub fullscreen: bool,
+    /// Most frames drawn per second, or 0 for no limit
+    pub frame_cap: u32,
+    /// Seconds each screen takes to fade in from black, 0.0 to cut straight to it
+    pub fade_time: f32,
+    /// Preset picked on the title menu
+    pub difficulty: Difficulty,
+}
+
+/// Which way round nodes are orbited
+#[derive(Debug, Copy, Clone, PartialEq, Serialize, Deserialize)]
+pub enum OrbitDirection {
+    /// Carry on the way the player is heading, as in classic play
+    Auto,
+    Clockwise,
+    Anticlockwise,
+}
+
+impl OrbitDirection {
+    /// The forced direction as the sim wants it, `Some(true)` being clockwise
+    pub fn lock(self) -> Option<bool> {
+        match self {
+            OrbitDirection::Auto => None,
+            OrbitDirection::Clockwise => Some(true),
+            OrbitDirection::Anticlockwise => Some(false),
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            OrbitDirection::Auto => "Automatic",
+            OrbitDirection::Clockwise => "Always clockwise",
+            OrbitDirection::Anticlockwise => "Always anticlockwise",
+        }
+    }
+
+    fn next(self, step: i32) -> OrbitDirection {
+        let all = [
+            OrbitDirection::Auto,
+            OrbitDirection::Clockwise,
+            OrbitDirection::Anticlockwise,
+        ];
+        cycle(&all, self, step > 0)
+    }
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            show_ghost: true,
+            adaptive_difficulty: false,
+            near_miss_feedback: true,
+            background_nodes: 60,
+            reseed_on_reset: false,
+            line_scale: None,
+            color_rules: false,
+            one_time_nodes: false,
+            volume: 1.0,
+            colorblind: false,
+            color_strategy: ColorStrategy::Sequential,
+            lanes: 0,
+            show_trail: true,
+            trail_width: 5.0,
+            show_last_trail: true,
+            glitch_trail: false,
+            rainbow_trail: false,
+            classic_background: false,
+            performance_mode: false,
+            scanlines: true,
+            node_sprites: false,
+            smooth_player: true,
+            wall_warning: false,
+            border_width: 5.0,
+            danger_zone: true,
+            launch_preview: true,
+            progress_bar: false,
+            constant_angular_speed: false,
+            smooth_orbit_entry: false,
+            attach_assist: 0.0,
+            alignment_meter: false,
+            max_orbit_time: 0.0,
+            orbit_decay: 0.0,
+            combo_decay: true,
+            combo_window: 2.0,
+            orbit_direction: OrbitDirection::Auto,
+            invert_vertical: false,
+            camera_lookahead: 1.0,
+            thrust_control: false,
+            panic_detach: false,
+            wind: false,
+            vsync: true,
+            fullscreen: false,
+            frame_cap: 0,
+            fade_time: 0.2,
+            difficulty: Difficulty::Normal,
+        }
+    }
+}
+
+impl Settings {
+    pub fn load(path: &Path) -> Result<Settings> {
+        read_json(path)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_json(path, self)
+    }
+}
+
+/// Rows of the in-game settings screen
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum SettingItem {
+    Volume,
+    Colorblind,
+    ColorStrategy,
+    Lanes,
+    ShowTrail,
+    TrailWidth,
+    ShowLastTrail,
+    GlitchTrail,
+    RainbowTrail,
+    ClassicBackground,
+    PerformanceMode,
+    Scanlines,
+    NodeSprites,
+    SmoothPlayer,
+    InvertVertical,
+    CameraLookahead,
+    WallWarning,
+    BorderWidth,
+    DangerZone,
+    LaunchPreview,
+    ProgressBar,
+    AdaptiveDifficulty,
+    ShowGhost,
+    NearMissFeedback,
+    ColorRules,
+    OneTimeNodes,
+    ConstantAngularSpeed,
+    SmoothOrbitEntry,
+    AttachAssist,
+    AlignmentMeter,
+    MaxOrbitTime,
+    OrbitDecay,
+    ComboDecay,
+    ComboWindow,
+    OrbitDirection,
+    ThrustControl,
+    PanicDetach,
+    Wind,
+    ReseedOnReset,
+    Vsync,
+    FrameCap,
+    FadeTime,
+    Back,
+}
+
+/// Widest the trail and walls can be made, in pixels
+const MAX_LINE_WIDTH: f32 = 15.0;
+
+/// Bounds on `Settings::camera_lookahead`. The lower one keeps the whole
+/// player on screen, the upper one keeps some of the course ahead in view.
+pub const MIN_CAMERA_LOOKAHEAD: f32 = 0.25;
+pub const MAX_CAMERA_LOOKAHEAD: f32 = 2.5;
+
+/// Lane counts the settings screen steps through, 0 being no lanes
+const LANE_COUNTS: [u32; 3] = [0, 3, 5];
+
+/// Orbit time limits the settings screen steps through, 0.0 being none
+const ORBIT_TIME_LIMITS: [f32; 6] = [0.0, 1.0, 1.5, 2.0, 3.0, 5.0];
+
+/// Orbit decay rates the settings screen steps through, with their names
+const ORBIT_DECAY_RATES: [(f32, &str); 4] =
+    [(0.0, "Off"), (0.05, "Slow"), (0.1, "Medium"), (0.2, "Fast")];
+
+/// Combo windows the settings screen steps through, in seconds
+const COMBO_WINDOWS: [f32; 5] = [1.0, 1.5, 2.0, 3.0, 5.0];
+
+/// Frame rate limits the settings screen steps through, 0 being unlimited
+const FRAME_CAPS: [u32; 5] = [0, 30, 60, 120, 144];
+
+/// Fade lengths the settings screen steps through, 0.0 being no fade
+const FADE_TIMES: [f32; 4] = [0.0, 0.2, 0.4, 0.6];
+
+pub const SETTING_ITEMS: [SettingItem; 43] = [
+    SettingItem::Volume,
+    SettingItem::Colorblind,
+    SettingItem::ColorStrategy,
+    SettingItem::Lanes,
+    SettingItem::ShowTrail,
+    SettingItem::TrailWidth,
+    SettingItem::ShowLastTrail,
+    SettingItem::GlitchTrail,
+    SettingItem::RainbowTrail,
+    SettingItem::ClassicBackground,
+    SettingItem::PerformanceMode,
+    SettingItem::Scanlines,
+    SettingItem::NodeSprites,
+    SettingItem::SmoothPlayer,
+    SettingItem::InvertVertical,
+    SettingItem::CameraLookahead,
+    SettingItem::WallWarning,
+    SettingItem::BorderWidth,
+    SettingItem::DangerZone,
+    SettingItem::LaunchPreview,
+    SettingItem::ProgressBar,
+    SettingItem::AdaptiveDifficulty,
+    SettingItem::ShowGhost,
+    SettingItem::NearMissFeedback,
+    SettingItem::ColorRules,
+    SettingItem::OneTimeNodes,
+    SettingItem::ConstantAngularSpeed,
+    SettingItem::SmoothOrbitEntry,
+    SettingItem::AttachAssist,
+    SettingItem::AlignmentMeter,
+    SettingItem::MaxOrbitTime,
+    SettingItem::OrbitDecay,
+    SettingItem::ComboDecay,
+    SettingItem::ComboWindow,
+    SettingItem::OrbitDirection,
+    SettingItem::ThrustControl,
+    SettingItem::PanicDetach,
+    SettingItem::Wind,
+    SettingItem::ReseedOnReset,
+    SettingItem::Vsync,
+    SettingItem::FrameCap,
+    SettingItem::FadeTime,
+    SettingItem::Back,
+];
+
+fn on_off(value: bool) -> &'static str {
+    if value {
+        "On"
+    } else {
+        "Off"
+    }
+}
+
+impl Settings {
+    pub fn label(&self, item: SettingItem) -> String {
+        match item {
+            SettingItem::Volume => format!("Volume: {:.0}%", self.volume * 100.0),
+            SettingItem::Colorblind => format!("Colorblind palette: {}", on_off(self.colorblind)),
+            SettingItem::ColorStrategy => {
+                format!("Node colours: {}", self.color_strategy.name())
+            }
+            SettingItem::Lanes => match self.lanes {
+                0 => "Node layout: Free".to_string(),
+                lanes => format!("Node layout: {} lanes", lanes),
+            },
+            SettingItem::ShowTrail => format!("Trail: {}", on_off(self.show_trail)),
+            SettingItem::TrailWidth => format!("Trail width: {:.0}", self.trail_width),
+            SettingItem::ShowLastTrail => {
+                format!("Previous run's trail: {}", on_off(self.show_last_trail))
+            }
+            SettingItem::GlitchTrail => format!("Glitchy trail: {}", on_off(self.glitch_trail)),
+            SettingItem::RainbowTrail => {
+                format!("Rainbow trail: {}", on_off(self.rainbow_trail))
+            }
+            SettingItem::ClassicBackground => {
+                format!(
+                    "Plain black background: {}",
+                    on_off(self.classic_background)
+                )
+            }
+            SettingItem::PerformanceMode => format!(
+                "Performance (faster, plainer look): {}",
+                on_off(self.performance_mode)
+            ),
+            SettingItem::Scanlines => format!("Background lines: {}", on_off(self.scanlines)),
+            SettingItem::NodeSprites => format!(
+                "Nodes: {}",
+                if self.node_sprites {
+                    "Sprites"
+                } else {
+                    "Circles"
+                }
+            ),
+            SettingItem::SmoothPlayer => format!(
+                "Player sprite: {}",
+                if self.smooth_player {
+                    "Smooth"
+                } else {
+                    "Pixelated"
+                }
+            ),
+            SettingItem::InvertVertical => {
+                format!("Fly downwards: {}", on_off(self.invert_vertical))
+            }
+            SettingItem::CameraLookahead => {
+                format!("Player height on screen: {:.2}", self.camera_lookahead)
+            }
+            SettingItem::WallWarning => format!("Wall warning: {}", on_off(self.wall_warning)),
+            SettingItem::BorderWidth => format!("Wall width: {:.0}", self.border_width),
+            SettingItem::DangerZone => format!("Wall danger zone: {}", on_off(self.danger_zone)),
+            SettingItem::LaunchPreview => {
+                format!("Launch preview: {}", on_off(self.launch_preview))
+            }
+            SettingItem::ProgressBar => format!("Progress bar: {}", on_off(self.progress_bar)),
+            SettingItem::AdaptiveDifficulty => {
+                format!("Adaptive difficulty: {}", on_off(self.adaptive_difficulty))
+            }
+            SettingItem::ShowGhost => format!("Best run ghost: {}", on_off(self.show_ghost)),
+            SettingItem::NearMissFeedback => {
+                format!("Near miss flash: {}", on_off(self.near_miss_feedback))
+            }
+            SettingItem::ColorRules => format!("Colour rules: {}", on_off(self.color_rules)),
+            SettingItem::OneTimeNodes => format!("One-time nodes: {}", on_off(self.one_time_nodes)),
+            SettingItem::ConstantAngularSpeed => format!(
+                "Same turn rate for every orbit: {}",
+                on_off(self.constant_angular_speed)
+            ),
+            SettingItem::SmoothOrbitEntry => {
+                format!("Smooth orbit entry: {}", on_off(self.smooth_orbit_entry))
+            }
+            SettingItem::AttachAssist if self.attach_assist <= 0.0 => {
+                "Attach assist: Off".to_string()
+            }
+            SettingItem::AttachAssist => {
+                format!("Attach assist: {:.0}%", self.attach_assist * 100.0)
+            }
+            SettingItem::AlignmentMeter => {
+                format!("Alignment meter: {}", on_off(self.alignment_meter))
+            }
+            SettingItem::MaxOrbitTime if self.max_orbit_time <= 0.0 => {
+                "Orbit time limit: Off".to_string()
+            }
+            SettingItem::MaxOrbitTime => {
+                format!("Orbit time limit: {:.1}s", self.max_orbit_time)
+            }
+            SettingItem::OrbitDecay => {
+                let name = ORBIT_DECAY_RATES
+                    .iter()
+                    .position(|&(rate, _)| rate == self.orbit_decay)
+                    .map_or("Custom", |i| ORBIT_DECAY_RATES[i].1);
+                format!("Orbit decay: {}", name)
+            }
+            SettingItem::ComboDecay => format!("Combo timer: {}", on_off(self.combo_decay)),
+            SettingItem::ComboWindow => format!("Combo window: {:.1}s", self.combo_window),
+            SettingItem::OrbitDirection => {
+                format!("Orbit direction: {}", self.orbit_direction.name())
+            }
+            SettingItem::ThrustControl => format!(
+                "Controls: {}",
+                if self.thrust_control {
+                    "Thrust"
+                } else {
+                    "Fixed speed"
+                }
+            ),
+            SettingItem::PanicDetach => {
+                format!("Panic detach (X): {}", on_off(self.panic_detach))
+            }
+            SettingItem::Wind => format!("Wind: {}", on_off(self.wind)),
+            SettingItem::ReseedOnReset => {
+                format!("New course each death: {}", on_off(self.reseed_on_reset))
+            }
+            SettingItem::Vsync => format!("Vsync (needs restart): {}", on_off(self.vsync)),
+            SettingItem::FrameCap => match self.frame_cap {
+                0 => "Frame rate cap: Off".to_string(),
+                fps => format!("Frame rate cap: {} fps", fps),
+            },
+            SettingItem::FadeTime if self.fade_time <= 0.0 => "Screen fades: Off".to_string(),
+            SettingItem::FadeTime => format!("Screen fades: {:.1}s", self.fade_time),
+            SettingItem::Back => "Back".to_string(),
+        }
+    }
+
+    /// Changes `item` by one step, `step` being -1 or 1. Toggles flip either way.
+    pub fn adjust(&mut self, item: SettingItem, step: i32) {
+        match item {
+            SettingItem::Volume => {
+                self.volume = (self.volume + 0.1 * step as f32).clamp(0.0, 1.0);
+            }
+            SettingItem::Colorblind => self.colorblind = !self.colorblind,
+            SettingItem::ColorStrategy => self.color_strategy = self.color_strategy.cycle(step),
+            SettingItem::Lanes => self.lanes = cycle(&LANE_COUNTS, self.lanes, step > 0),
+            SettingItem::ShowTrail => self.show_trail = !self.show_trail,
+            SettingItem::TrailWidth => {
+                self.trail_width = (self.trail_width + step as f32).clamp(1.0, MAX_LINE_WIDTH);
+            }
+            SettingItem::ShowLastTrail => self.show_last_trail = !self.show_last_trail,
+            SettingItem::GlitchTrail => self.glitch_trail = !self.glitch_trail,
+            SettingItem::RainbowTrail => self.rainbow_trail = !self.rainbow_trail,
+            SettingItem::ClassicBackground => self.classic_background = !self.classic_background,
+            SettingItem::PerformanceMode => self.performance_mode = !self.performance_mode,
+            SettingItem::Scanlines => self.scanlines = !self.scanlines,
+            SettingItem::NodeSprites => self.node_sprites = !self.node_sprites,
+            SettingItem::SmoothPlayer => self.smooth_player = !self.smooth_player,
+            SettingItem::InvertVertical => self.invert_vertical = !self.invert_vertical,
+            SettingItem::CameraLookahead => {
+                self.camera_lookahead = (self.camera_lookahead + 0.25 * step as f32)
+                    .clamp(MIN_CAMERA_LOOKAHEAD, MAX_CAMERA_LOOKAHEAD);
+            }
+            SettingItem::WallWarning => self.wall_warning = !self.wall_warning,
+            SettingItem::BorderWidth => {
+                self.border_width = (self.border_width + step as f32).clamp(1.0, MAX_LINE_WIDTH);
+            }
+            SettingItem::DangerZone => self.danger_zone = !self.danger_zone,
+            SettingItem::LaunchPreview => self.launch_preview = !self.launch_preview,
+            SettingItem::ProgressBar => self.progress_bar = !self.progress_bar,
+            SettingItem::AdaptiveDifficulty => {
+                self.adaptive_difficulty = !self.adaptive_difficulty;
+            }
+            SettingItem::ShowGhost => self.show_ghost = !self.show_ghost,
+            SettingItem::NearMissFeedback => self.near_miss_feedback = !self.near_miss_feedback,
+            SettingItem::ColorRules => self.color_rules = !self.color_rules,
+            SettingItem::OneTimeNodes => self.one_time_nodes = !self.one_time_nodes,
+            SettingItem::ConstantAngularSpeed => {
+                self.constant_angular_speed = !self.constant_angular_speed;
+            }
+            SettingItem::SmoothOrbitEntry => self.smooth_orbit_entry = !self.smooth_orbit_entry,
+            SettingItem::AttachAssist => {
+                self.attach_assist = (self.attach_assist + 0.25 * step as f32).clamp(0.0, 1.0);
+            }
+            SettingItem::AlignmentMeter => self.alignment_meter = !self.alignment_meter,
+            SettingItem::MaxOrbitTime => {
+                self.max_orbit_time = cycle(&ORBIT_TIME_LIMITS, self.max_orbit_time, step > 0)
+            }
+            SettingItem::OrbitDecay => {
+                let rates = ORBIT_DECAY_RATES.map(|(rate, _)| rate);
+                self.orbit_decay = cycle(&rates, self.orbit_decay, step > 0);
+            }
+            SettingItem::ComboDecay => self.combo_decay = !self.combo_decay,
+            SettingItem::ComboWindow => {
+                self.combo_window = cycle(&COMBO_WINDOWS, self.combo_window, step > 0)
+            }
+            SettingItem::OrbitDirection => self.orbit_direction = self.orbit_direction.next(step),
+            SettingItem::ThrustControl => self.thrust_control = !self.thrust_control,
+            SettingItem::PanicDetach => self.panic_detach = !self.panic_detach,
+            SettingItem::Wind => self.wind = !self.wind,
+            SettingItem::ReseedOnReset => self.reseed_on_reset = !self.reseed_on_reset,
+            SettingItem::Vsync => self.vsync = !self.vsync,
+            SettingItem::FrameCap => self.frame_cap = cycle(&FRAME_CAPS, self.frame_cap, step > 0),
+            SettingItem::FadeTime => self.fade_time = cycle(&FADE_TIMES, self.fade_time, step > 0),
+            SettingItem::Back => {}
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::error::{Error, Result};
+use crate::save::{read_json, write_json};
+use crate::Sim;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Bump whenever `Sim` changes shape, so old snapshots are turned away
+/// with a clear message rather than restoring half a state
+pub const SNAPSHOT_VERSION: u32 = 9;
+
+/// A run frozen at one instant, for save states and rewinding
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub version: u32,
+    pub sim: Sim,
+    /// `Node::used` isn't saved with the nodes, so it's kept alongside them
+    pub used: Vec<bool>,
+    /// `Rand32` state, so whatever's generated next comes out the same
+    pub rng: (u64, u64),
+}
+
+impl Snapshot {
+    /// Loads a saved snapshot, or `None` if there isn't one
+    pub fn load(path: &Path) -> Result<Option<Snapshot>> {
+        let Some(json) = read_json::<Option<serde_json::Value>>(path)? else {
+            return Ok(None);
+        };
+        // Check the version before anything else, since an older snapshot
+        // might not even parse
+        let found = json.get("version").and_then(|v| v.as_u64());
+        if found != Some(SNAPSHOT_VERSION as u64) {
+            return Err(Error::SnapshotVersion {
+                path: path.to_path_buf(),
+                found,
+            });
+        }
+        serde_json::from_value(json).map_err(|source| Error::ConfigParse {
+            path: path.to_path_buf(),
+            source,
+        })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        write_json(path, self)
+    }
+}
@@ -0,0 +1,45 @@
+use crate::error::{Error, Result};
+use crate::{CollisionCause, GameMode};
+use serde::Serialize;
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+
+/// Past this size the log is moved aside to `.old`, replacing any older one
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+/// One line of the balancing log, written as JSON when a run ends
+#[derive(Debug, Serialize)]
+pub struct RunRecord<'a> {
+    pub course_id: &'a str,
+    pub seed: Option<u64>,
+    pub mode: GameMode,
+    pub height: f32,
+    pub time: f32,
+    pub attaches: u32,
+    /// `None` when the run ended without a crash, e.g. by quitting to the menu
+    pub cause: CollisionCause,
+}
+
+pub fn append(path: &Path, record: &RunRecord) -> Result<()> {
+    let io_error = |source: io::Error| Error::SaveLoad {
+        path: path.to_path_buf(),
+        source,
+    };
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(io_error)?;
+    }
+    if fs::metadata(path).is_ok_and(|m| m.len() > MAX_LOG_BYTES) {
+        fs::rename(path, path.with_extension("old")).map_err(io_error)?;
+    }
+    let line = serde_json::to_string(record).map_err(|source| Error::ConfigParse {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(io_error)?;
+    writeln!(file, "{}", line).map_err(io_error)
+}